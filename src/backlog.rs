@@ -27,6 +27,15 @@ impl Backlog {
         }
     }
 
+    /// Returns `true` if there's at least one backlogged [`Insertion`].
+    #[cfg(feature = "text-replica")]
+    #[inline]
+    pub fn has_insertions(&self) -> bool {
+        self.insertions
+            .iter()
+            .any(|(_, backlog)| !backlog.insertions.is_empty())
+    }
+
     /// Inserts a new [`Deletion`] into the backlog.
     ///
     /// Runs in `O(n)` in the number of deletions already in the backlog, with
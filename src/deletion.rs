@@ -51,8 +51,25 @@ impl Deletion {
         self.version_map.this_id()
     }
 
+    /// Returns the [`DeletionTs`] of this deletion.
+    ///
+    /// Together with the [`deleted_by`](Deletion::deleted_by) this uniquely
+    /// identifies the deletion.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use cola::Replica;
+    /// let mut replica1 = Replica::new(1, 10);
+    ///
+    /// let first = replica1.deleted(3..7);
+    /// let second = replica1.deleted(0..2);
+    ///
+    /// assert_eq!(first.deletion_ts(), 1);
+    /// assert_eq!(second.deletion_ts(), 2);
+    /// ```
     #[inline(always)]
-    pub(crate) fn deletion_ts(&self) -> DeletionTs {
+    pub fn deletion_ts(&self) -> DeletionTs {
         self.deletion_ts
     }
 
@@ -93,7 +110,7 @@ impl Deletion {
 }
 
 #[cfg(feature = "encode")]
-mod encode {
+pub(crate) mod encode {
     use super::*;
     use crate::encode::{Decode, Encode, IntDecodeError};
     use crate::version_map::encode::BaseMapDecodeError;
@@ -186,7 +186,6 @@ impl<L> LeafIdx<L> {
         Self::new(usize::MAX)
     }
 
-    #[cfg(feature = "encode")]
     #[inline]
     pub(crate) fn into_usize(self) -> usize {
         self.idx
@@ -536,7 +535,6 @@ impl<const ARITY: usize, L: Leaf> Gtree<ARITY, L> {
         self.lnode(leaf_idx).value()
     }
 
-    #[cfg(feature = "encode")]
     #[inline(always)]
     pub fn num_leaves(&self) -> usize {
         self.lnodes.len()
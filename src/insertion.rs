@@ -103,7 +103,7 @@ impl Insertion {
 }
 
 #[cfg(feature = "encode")]
-mod encode {
+pub(crate) mod encode {
     use super::*;
     use crate::encode::{BoolDecodeError, Decode, Encode, IntDecodeError};
 
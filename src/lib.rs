@@ -109,7 +109,12 @@
 //!   [`decode`](Replica::decode) methods on [`Replica`] (disabled by default);
 //!
 //! - `serde`: enables the [`Serialize`] and [`Deserialize`] impls for
-//!   [`Insertion`], [`Deletion`] and [`EncodedReplica`] (disabled by default).
+//!   [`Insertion`], [`Deletion`] and [`EncodedReplica`] (disabled by default);
+//!
+//! - `text-replica`: enables the [`TextReplica`] type, a [`Replica`] which
+//!   also owns the text of the document (disabled by default). If the `serde`
+//!   feature is also enabled, its [`TextEdit`]s can be serialized and
+//!   deserialized.
 //!
 //! [CRDT]: https://en.wikipedia.org/wiki/Conflict-free_replicated_data_type
 //! [cola]: https://nomad.foo/blog/cola
@@ -142,7 +147,12 @@ mod replica_id;
 mod run_indices;
 mod run_tree;
 mod text;
+#[cfg(feature = "text-replica")]
+mod text_replica;
+mod tombstones;
+mod undo;
 mod utils;
+mod version;
 mod version_map;
 
 use anchor::*;
@@ -154,14 +164,19 @@ pub use deletion::Deletion;
 pub use encoded_replica::{DecodeError, EncodedReplica};
 use gtree::{Gtree, LeafIdx};
 pub use insertion::Insertion;
-pub use replica::Replica;
 use replica::*;
+pub use replica::{Compaction, DeletionTs, Replica};
 pub use replica_id::ReplicaId;
 use replica_id::{ReplicaIdMap, ReplicaIdMapValuesMut};
 use run_indices::RunIndices;
 use run_tree::*;
 pub use text::Text;
+#[cfg(feature = "text-replica")]
+pub use text_replica::{TextEdit, TextReplica};
+use tombstones::{DeletedBy, Tombstones};
+pub use undo::{UndoEdit, UndoManager};
 use utils::*;
+pub use version::{MissingEdits, Version};
 use version_map::{DeletionMap, VersionMap};
 
 /// The version of the protocol cola uses to represent `EncodedReplica`s and
@@ -273,4 +288,4 @@ pub type Length = usize;
 ///
 /// See [`ProtocolVersion`] for more infos.
 #[cfg(feature = "encode")]
-const PROTOCOL_VERSION: ProtocolVersion = 3;
+const PROTOCOL_VERSION: ProtocolVersion = 4;
@@ -200,6 +200,12 @@ impl Replica {
         BackloggedInsertions::from_replica(self)
     }
 
+    #[cfg(feature = "text-replica")]
+    #[inline]
+    pub(crate) fn backlog(&self) -> &Backlog {
+        &self.backlog
+    }
+
     #[inline]
     pub(crate) fn backlog_mut(&mut self) -> &mut Backlog {
         &mut self.backlog
@@ -246,6 +252,74 @@ impl Replica {
         )
     }
 
+    /// Removes the deleted text that can no longer be referenced by any edit,
+    /// given a causally stable frontier, and merges the internal runs that
+    /// can be joined once that text is gone.
+    ///
+    /// Every deleted character is kept around by the `Replica` so that remote
+    /// edits made concurrently with its deletion can still be integrated, and
+    /// long-running documents can accumulate many of them. Once every peer
+    /// has integrated a deletion no new edit can refer to the deleted text,
+    /// and this method can be used to reclaim it.
+    ///
+    /// # Causal stability
+    ///
+    /// The given [`Version`] has to be causally stable: every peer in the
+    /// session must have already integrated all the edits it includes, and
+    /// every edit that it doesn't include must have been created by a peer
+    /// that had already integrated all the ones it does.
+    ///
+    /// A common way to get one is to take the [`meet`](Version::meet) of the
+    /// latest `Version`s reported by every peer, as long as each reported
+    /// `Version` is only taken into account once all the edits its peer sent
+    /// before it have been integrated (i.e. not just backlogged) by this
+    /// `Replica`.
+    ///
+    /// Passing a `Version` that isn't causally stable can cause remote edits
+    /// to be integrated at the wrong offset, or the `Replica` to panic when
+    /// integrating them.
+    ///
+    /// # Anchors and undo
+    ///
+    /// [`Anchor`]s pointing into the removed text keep resolving to the
+    /// offset where that text used to be. However, the removed text can't be
+    /// re-inserted by an [`UndoManager`] anymore.
+    ///
+    /// # Memory usage
+    ///
+    /// Only the runs holding the removed text are freed. The `Replica` still
+    /// keeps a small record of the temporal range of every removed run so
+    /// that the edits and anchors referring to it keep working, and those
+    /// records are also part of its encoding. Because of this, neither the
+    /// memory used by the `Replica` nor the size of an `EncodedReplica`
+    /// shrink in proportion to the number of removed runs.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use cola::Replica;
+    /// let mut peer_1 = Replica::new(1, 10);
+    /// let mut peer_2 = peer_1.fork(2);
+    ///
+    /// let deletion = peer_1.deleted(2..5);
+    /// peer_2.integrate_deletion(&deletion);
+    ///
+    /// // Both peers have integrated the deletion, so no future edit can
+    /// // refer to the deleted text.
+    /// let frontier = peer_1.version().meet(&peer_2.version());
+    ///
+    /// let compaction = peer_1.compact(&frontier);
+    /// assert_eq!(compaction.removed_runs(), 1);
+    /// ```
+    #[inline]
+    pub fn compact(&mut self, frontier: &Version) -> Compaction {
+        let (removed_runs, merged_runs) = self
+            .run_tree
+            .compact(frontier.version_map(), frontier.deletion_map());
+
+        Compaction { removed_runs, merged_runs }
+    }
+
     /// Creates a new [`Anchor`] at the given offset, with the given bias.
     ///
     /// You can think of an `Anchor` as a sticky line cursor that you can
@@ -465,8 +539,10 @@ impl Replica {
 
         let mut version_map = VersionMap::new(self.id(), 0);
 
+        let deleted_by = DeletedBy::new(self.id, self.deletion_map.this() + 1);
+
         let (start, end) =
-            self.run_tree.delete(deleted_range, &mut version_map);
+            self.run_tree.delete(deleted_range, &mut version_map, deleted_by);
 
         for (id, ts) in version_map.iter_mut() {
             *ts = self.version_map.get(id);
@@ -569,7 +645,7 @@ impl Replica {
     /// Returns `true` if this `Replica` has already merged the given
     /// `Insertion`.
     #[inline]
-    fn has_merged_insertion(&self, insertion: &Insertion) -> bool {
+    pub(crate) fn has_merged_insertion(&self, insertion: &Insertion) -> bool {
         self.version_map.get(insertion.inserted_by()) > insertion.start()
     }
 
@@ -635,6 +711,39 @@ impl Replica {
         )
     }
 
+    /// Inserts some new text of the given length right after the character
+    /// the given anchor points to, returning the [`Insertion`] to send to
+    /// the other peers together with the offset at which the text ended up.
+    ///
+    /// Unlike [`inserted`](Self::inserted), this places the text exactly
+    /// where the other peers will place it even when the anchor is deleted
+    /// and there are other runs at the same visible offset.
+    #[inline]
+    pub(crate) fn inserted_after(
+        &mut self,
+        anchor: InnerAnchor,
+        len: Length,
+    ) -> (Insertion, Length) {
+        debug_assert!(len > 0);
+
+        let start = self.version_map.this();
+
+        *self.version_map.this_mut() += len;
+
+        let end = self.version_map.this();
+
+        let insertion = Insertion::new(
+            anchor,
+            Text::new(self.id, start..end),
+            self.lamport_clock.next(),
+            self.run_clock.next(),
+        );
+
+        let offset = self.run_tree.merge_insertion(&insertion);
+
+        (insertion, offset)
+    }
+
     #[allow(clippy::len_without_is_empty)]
     #[doc(hidden)]
     pub fn len(&self) -> Length {
@@ -816,6 +925,60 @@ impl Replica {
         offset
     }
 
+    /// Returns the [`MissingEdits`] containing all the edits that this
+    /// `Replica` has integrated but that are not included in the given
+    /// [`Version`].
+    ///
+    /// This is meant to be used when two peers reconnect after being
+    /// disconnected for a while: each peer sends its [`version`] to the
+    /// other, which can then look up the missing edits in its own log of the
+    /// [`Insertion`]s and [`Deletion`]s it has seen and only send those.
+    ///
+    /// [`version`]: Replica::version
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use cola::Replica;
+    /// let mut peer_1 = Replica::new(1, 10);
+    /// let mut peer_2 = peer_1.fork(2);
+    ///
+    /// let insertion = peer_1.inserted(5, 3);
+    /// let deletion = peer_1.deleted(..2);
+    ///
+    /// let missing = peer_1.missing_since(&peer_2.version());
+    ///
+    /// assert_eq!(missing.insertions(), [insertion.text().clone()]);
+    /// assert_eq!(missing.deletions(), [(1, 1..2)]);
+    ///
+    /// peer_2.integrate_insertion(&insertion);
+    /// peer_2.integrate_deletion(&deletion);
+    ///
+    /// assert!(peer_1.missing_since(&peer_2.version()).is_empty());
+    /// ```
+    #[inline]
+    pub fn missing_since(&self, version: &Version) -> MissingEdits {
+        let insertions = self
+            .version_map
+            .iter()
+            .filter_map(|(id, ours)| {
+                let theirs = version.version_map().get(id);
+                (ours > theirs).then(|| Text::new(id, theirs..ours))
+            })
+            .collect();
+
+        let deletions = self
+            .deletion_map
+            .iter()
+            .filter_map(|(id, ours)| {
+                let theirs = version.deletion_map().get(id);
+                (ours > theirs).then(|| (id, theirs + 1..ours + 1))
+            })
+            .collect();
+
+        MissingEdits::new(insertions, deletions)
+    }
+
     /// Creates a new `Replica` with the given [`ReplicaId`] from the initial
     /// [`Length`] of your buffer.
     ///
@@ -935,6 +1098,70 @@ impl Replica {
             None
         }
     }
+
+    #[inline]
+    pub(crate) fn run_tree(&self) -> &RunTree {
+        &self.run_tree
+    }
+
+    /// Returns a [`Version`] containing all the edits that this `Replica` has
+    /// integrated so far.
+    ///
+    /// See [`missing_since`](Replica::missing_since) for how to use it to
+    /// synchronize two peers.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use cola::Replica;
+    /// let mut peer_1 = Replica::new(1, 10);
+    /// let mut peer_2 = peer_1.fork(2);
+    ///
+    /// let _ = peer_1.deleted(..5);
+    ///
+    /// // The meet of the two versions doesn't include the deletion since
+    /// // peer 2 hasn't integrated it yet.
+    /// let frontier = peer_1.version().meet(&peer_2.version());
+    /// assert_eq!(peer_1.compact(&frontier).removed_runs(), 0);
+    /// ```
+    #[inline]
+    pub fn version(&self) -> Version {
+        Version::new(
+            self.version_map.clone(),
+            self.deletion_map.clone(),
+            self.lamport_clock.highest(),
+        )
+    }
+}
+
+/// A report of the work done by [`Replica::compact`].
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Compaction {
+    removed_runs: usize,
+    merged_runs: usize,
+}
+
+impl Compaction {
+    /// Returns the number of internal runs that were merged into the run
+    /// preceding them after the deleted text between them was removed.
+    #[inline]
+    pub fn merged_runs(&self) -> usize {
+        self.merged_runs
+    }
+
+    /// Returns the number of internal runs of deleted text that were
+    /// removed.
+    #[inline]
+    pub fn removed_runs(&self) -> usize {
+        self.removed_runs
+    }
+
+    /// Returns the total number of internal runs that were reclaimed, i.e.
+    /// the number of runs the `Replica` stores that went away.
+    #[inline]
+    pub fn reclaimed_runs(&self) -> usize {
+        self.removed_runs + self.merged_runs
+    }
 }
 
 impl core::fmt::Debug for Replica {
@@ -1032,6 +1259,11 @@ impl RunClock {
     }
 }
 
+/// The timestamp of a [`Deletion`], unique among all the deletions performed
+/// by the same peer.
+///
+/// The timestamps of the deletions performed by a given peer start at 1 and
+/// are incremented by 1 on every deletion.
 pub type DeletionTs = u64;
 
 #[cfg(feature = "encode")]
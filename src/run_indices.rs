@@ -1,4 +1,5 @@
 use core::ops::{Index, IndexMut};
+use std::collections::BTreeMap;
 
 use crate::anchor::InnerAnchor as Anchor;
 use crate::*;
@@ -25,6 +26,11 @@ impl RunIndices {
 
             for (idx, splits) in indices.splits().enumerate() {
                 for split in splits.iter() {
+                    if indices.collected_at(offset).is_some() {
+                        offset += split.len;
+                        continue;
+                    }
+
                     let run = run_tree.run(split.idx);
                     assert_eq!(replica_id, run.replica_id());
                     assert_eq!(split.len, run.len());
@@ -36,6 +42,31 @@ impl RunIndices {
         }
     }
 
+    /// Records that the given [`Text`] was removed from the [`RunTree`] by a
+    /// compaction, and that the character at the given [`Anchor`] was the
+    /// first one after it at the time.
+    #[inline]
+    pub fn collect(&mut self, text: &Text, next: Anchor) {
+        self.get_mut(text.inserted_by()).collect(text.range.clone(), next);
+    }
+
+    /// Returns the [`Anchor`] of the character that followed the character
+    /// the given [`Anchor`] refers to when it was removed by a compaction, or
+    /// `None` if it hasn't been removed.
+    #[inline]
+    pub fn collected(
+        &self,
+        anchor: Anchor,
+        bias: AnchorBias,
+    ) -> Option<Anchor> {
+        let offset = match bias {
+            AnchorBias::Left => anchor.offset().checked_sub(1)?,
+            AnchorBias::Right => anchor.offset(),
+        };
+
+        self.map.get(&anchor.replica_id())?.collected_at(offset)
+    }
+
     #[inline]
     pub fn get_mut(&mut self, id: ReplicaId) -> &mut ReplicaIndices {
         self.map.entry(id).or_default()
@@ -65,10 +96,33 @@ impl RunIndices {
         self.map.iter()
     }
 
+    /// Returns the [`LeafIdx`]s of all the [`EditRun`]s containing some part
+    /// of the given [`Text`], sorted by their temporal offset.
+    #[inline]
+    pub fn leaves_of_text(&self, text: &Text) -> Vec<LeafIdx<EditRun>> {
+        self.map
+            .get(&text.inserted_by())
+            .map(|indices| indices.leaves_in_range(text.range.clone()))
+            .unwrap_or_default()
+    }
+
     #[inline]
     pub fn new() -> Self {
         Self { map: ReplicaIdMap::default() }
     }
+
+    /// Replaces the [`LeafIdx`] of every fragment with the one returned by
+    /// the given function, merging the adjacent fragments of a run that end
+    /// up pointing to the same leaf.
+    #[inline]
+    pub fn remap<F>(&mut self, mut remap_idx: F)
+    where
+        F: FnMut(LeafIdx<EditRun>) -> LeafIdx<EditRun>,
+    {
+        for indices in self.map.values_mut() {
+            indices.remap(&mut remap_idx);
+        }
+    }
 }
 
 /// Contains the [`LeafIdx`]s of all the [`EditRun`]s that have been inserted
@@ -86,6 +140,16 @@ pub(crate) struct ReplicaIndices {
     /// The `Length` field in the tuple is the cumulative length of all the
     /// previous [`Fragments`] up to but not including the current one.
     vec: Vec<(Fragments, Length)>,
+
+    /// The temporal ranges of the runs that were removed from the
+    /// [`RunTree`] by a compaction, keyed by their start and mapped to their
+    /// end and to the [`Anchor`] of the first character that followed them
+    /// at the time.
+    ///
+    /// The [`Fragment`]s covering these ranges are kept in `vec` so that the
+    /// temporal offsets of the other fragments don't change, but the
+    /// [`LeafIdx`]s they point to are meaningless.
+    collected: BTreeMap<Length, (Length, Anchor)>,
 }
 
 impl core::fmt::Debug for ReplicaIndices {
@@ -141,6 +205,14 @@ impl ReplicaIndices {
     }
 
     fn assert_invariants(&self) {
+        let mut collected_end = 0;
+
+        for (&start, &(end, _)) in self.collected.iter() {
+            assert!(collected_end <= start);
+            assert!(start < end);
+            collected_end = end;
+        }
+
         let mut offset = 0;
 
         for &(ref splits, splits_offset) in self.vec.iter() {
@@ -150,6 +222,42 @@ impl ReplicaIndices {
         }
     }
 
+    #[inline]
+    fn collect(&mut self, range: core::ops::Range<Length>, next: Anchor) {
+        let core::ops::Range { mut start, mut end } = range;
+
+        if let Some((&prev_start, &(prev_end, prev_next))) =
+            self.collected.range(..start).next_back()
+        {
+            if prev_end == start && prev_next == next {
+                start = prev_start;
+            }
+        }
+
+        if let Some(&(next_end, next_next)) = self.collected.get(&end) {
+            if next_next == next {
+                self.collected.remove(&end);
+                end = next_end;
+            }
+        }
+
+        self.collected.insert(start, (end, next));
+    }
+
+    #[inline]
+    pub fn collected_at(&self, offset: Length) -> Option<Anchor> {
+        let (_, &(end, next)) = self.collected.range(..=offset).next_back()?;
+        (offset < end).then_some(next)
+    }
+
+    #[cfg(feature = "encode")]
+    #[inline(always)]
+    pub(crate) fn collected_ranges(
+        &self,
+    ) -> impl ExactSizeIterator<Item = (Length, Length, Anchor)> + '_ {
+        self.collected.iter().map(|(&start, &(end, next))| (start, end, next))
+    }
+
     #[inline]
     pub fn extend_last(&mut self, extend_by: Length) {
         self.vec.last_mut().unwrap().0.extend(extend_by);
@@ -174,6 +282,44 @@ impl ReplicaIndices {
         self.vec.iter()
     }
 
+    /// Returns the [`LeafIdx`]s of all the [`EditRun`]s intersecting the
+    /// given temporal range, sorted by their temporal offset.
+    #[inline]
+    fn leaves_in_range(
+        &self,
+        range: core::ops::Range<Length>,
+    ) -> Vec<LeafIdx<EditRun>> {
+        let first = self
+            .vec
+            .partition_point(|&(_, offset)| offset <= range.start)
+            .saturating_sub(1);
+
+        let mut leaves = Vec::new();
+
+        for (fragments, offset) in &self.vec[first..] {
+            if *offset >= range.end {
+                break;
+            }
+
+            let mut fragment_start = *offset;
+
+            for fragment in fragments.iter() {
+                let fragment_end = fragment_start + fragment.len;
+
+                if fragment_end > range.start
+                    && fragment_start < range.end
+                    && self.collected_at(fragment_start).is_none()
+                {
+                    leaves.push(fragment.idx);
+                }
+
+                fragment_start = fragment_end;
+            }
+        }
+
+        leaves
+    }
+
     #[inline]
     pub fn len(&self) -> usize {
         self.vec.len()
@@ -203,8 +349,45 @@ impl ReplicaIndices {
 
     #[cfg(feature = "encode")]
     #[inline(always)]
-    pub(crate) fn new(vec: Vec<(Fragments, Length)>) -> Self {
-        Self { vec }
+    pub(crate) fn new(
+        vec: Vec<(Fragments, Length)>,
+        collected: BTreeMap<Length, (Length, Anchor)>,
+    ) -> Self {
+        Self { vec, collected }
+    }
+
+    #[inline]
+    fn remap<F>(&mut self, remap_idx: &mut F)
+    where
+        F: FnMut(LeafIdx<EditRun>) -> LeafIdx<EditRun>,
+    {
+        for run_idx in 0..self.vec.len() {
+            let (fragments, offset) = &self.vec[run_idx];
+
+            let mut remapped = Fragments::default();
+
+            let mut fragment_start = *offset;
+
+            let mut last = None;
+
+            for fragment in fragments.iter() {
+                let idx = remap_idx(fragment.idx);
+
+                let is_collected = self.collected_at(fragment_start).is_some();
+
+                if last == Some((idx, is_collected)) {
+                    remapped.extend(fragment.len);
+                } else {
+                    remapped.append(Fragment::new(fragment.len, idx));
+                }
+
+                last = Some((idx, is_collected));
+
+                fragment_start += fragment.len;
+            }
+
+            self.vec[run_idx].0 = remapped;
+        }
     }
 
     #[inline]
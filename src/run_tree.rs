@@ -17,6 +17,9 @@ pub(crate) struct RunTree {
     /// [`LeafIdx`](crate::LeafIdx) of the run that contains a given
     /// [`Anchor`].
     run_indices: RunIndices,
+
+    /// Keeps track of which deletion deleted each deleted run.
+    tombstones: Tombstones,
 }
 
 impl RunTree {
@@ -48,6 +51,20 @@ impl RunTree {
     pub fn assert_invariants(&self) {
         self.gtree.assert_invariants();
         self.run_indices.assert_invariants(self);
+        self.tombstones.assert_invariants();
+
+        for (_, run) in self.gtree.leaves_from_first() {
+            let deleted_len = self
+                .tombstones
+                .deleted_by(&run.text)
+                .iter()
+                .map(|(part, _)| part.len())
+                .sum::<Length>();
+
+            let expected_len = if run.is_deleted { run.len() } else { 0 };
+
+            assert_eq!(deleted_len, expected_len);
+        }
     }
 
     #[inline]
@@ -55,6 +72,117 @@ impl RunTree {
         self.gtree.average_inode_occupancy()
     }
 
+    /// Removes the deleted runs that can't be referenced by any future edit,
+    /// then merges the runs that end up next to each other and can be joined.
+    ///
+    /// A deleted run can be removed if the deletion that deleted it is
+    /// included in the given `DeletionMap`, and if the first run after it
+    /// that's not removed was inserted by an insertion included in the given
+    /// `VersionMap`. For the edits created after those maps to be sure to
+    /// never reference a removed run, both maps have to be causally stable,
+    /// i.e. every peer must have already integrated every edit they include.
+    ///
+    /// The last run in the tree is never removed.
+    ///
+    /// Returns the number of runs that were removed and the number of runs
+    /// that were merged into the run preceding them.
+    #[inline]
+    pub fn compact(
+        &mut self,
+        version_map: &VersionMap,
+        deletion_map: &DeletionMap,
+    ) -> (usize, usize) {
+        let runs = self.gtree.leaves_from_first().collect::<Vec<_>>();
+
+        let mut is_removed = vec![false; runs.len()];
+
+        let mut next_kept: Option<&EditRun> = None;
+
+        // We walk the runs backwards because whether a run can be removed
+        // depends on the first run after it that's kept.
+        for (idx, &(_, run)) in runs.iter().enumerate().rev() {
+            if let Some(next) = next_kept {
+                let is_deletion_stable = run.is_deleted
+                    && self.tombstones.is_stable(&run.text, deletion_map);
+
+                let is_next_stable =
+                    version_map.get(next.replica_id()) > next.start();
+
+                if is_deletion_stable && is_next_stable {
+                    is_removed[idx] = true;
+                    continue;
+                }
+            }
+
+            next_kept = Some(run);
+        }
+
+        if !is_removed.contains(&true) {
+            return (0, 0);
+        }
+
+        let mut num_removed = 0;
+
+        let mut num_merged = 0;
+
+        let mut kept = Vec::<EditRun>::new();
+
+        // Maps the `LeafIdx` of every run in the current tree to the position
+        // in `kept` of the run that either contains it or, if it's removed,
+        // of the first run after it.
+        let mut positions = vec![0; self.gtree.num_leaves()];
+
+        let mut removed = Vec::new();
+
+        for (&(leaf_idx, run), &is_removed) in runs.iter().zip(&is_removed) {
+            if is_removed {
+                removed.push((leaf_idx, run));
+                num_removed += 1;
+                continue;
+            }
+
+            match kept.last_mut() {
+                Some(last) if last.can_append(run) => {
+                    *last.end_mut() = run.end();
+                    num_merged += 1;
+                },
+
+                _ => kept.push(run.clone()),
+            }
+
+            let position = kept.len() - 1;
+
+            positions[leaf_idx.into_usize()] = position;
+
+            let next =
+                Anchor::new(run.replica_id(), run.start(), run.run_ts());
+
+            for (removed_idx, removed_run) in removed.drain(..) {
+                positions[removed_idx.into_usize()] = position;
+                self.run_indices.collect(&removed_run.text, next);
+                self.tombstones.remove(&removed_run.text);
+            }
+        }
+
+        debug_assert!(removed.is_empty());
+
+        let mut kept = kept.into_iter();
+
+        let (mut gtree, first_idx) =
+            Gtree::from_first_leaf(kept.next().expect("the last run is kept"));
+
+        let mut new_indices = vec![first_idx];
+
+        new_indices.extend(kept.map(|run| gtree.append(run)));
+
+        self.run_indices
+            .remap(|leaf_idx| new_indices[positions[leaf_idx.into_usize()]]);
+
+        self.gtree = gtree;
+
+        (num_removed, num_merged)
+    }
+
     #[inline]
     pub fn count_empty_leaves(&self) -> (usize, usize) {
         self.gtree.count_empty_leaves()
@@ -139,7 +267,12 @@ impl RunTree {
         &mut self,
         range: Range<Length>,
         version_map: &mut VersionMap,
+        deleted_by: DeletedBy,
     ) -> (Anchor, Anchor) {
+        for text in self.visible_texts(range.into()) {
+            self.tombstones.insert(&text, deleted_by);
+        }
+
         let mut id_start = 0;
         let mut run_ts_start = 0;
         let mut offset_start = 0;
@@ -274,6 +407,62 @@ impl RunTree {
         (anchor_start, anchor_end)
     }
 
+    /// Returns the parts of the given [`Text`] that are currently deleted,
+    /// each paired with an [`Anchor`] to its last character.
+    ///
+    /// The parts are sorted by their position in the document, and parts that
+    /// are only separated by other deleted text are joined together.
+    #[inline]
+    pub fn deleted_parts(&self, text: &Text) -> Vec<(Anchor, Text)> {
+        let mut parts = Vec::<(Length, Anchor, Text)>::new();
+
+        for (leaf_idx, part) in self.parts_of_text(text) {
+            let run = self.gtree.leaf(leaf_idx);
+
+            if !run.is_deleted {
+                continue;
+            }
+
+            let offset = self.gtree.offset_of_leaf(leaf_idx);
+
+            let anchor =
+                Anchor::new(part.inserted_by(), part.end(), run.run_ts());
+
+            if let Some((last_offset, last_anchor, last_part)) =
+                parts.last_mut()
+            {
+                if *last_offset == offset && last_part.end() == part.start() {
+                    last_part.range.end = part.end();
+                    *last_anchor = anchor;
+                    continue;
+                }
+            }
+
+            parts.push((offset, anchor, part));
+        }
+
+        parts.into_iter().map(|(_, anchor, part)| (anchor, part)).collect()
+    }
+
+    /// Returns the [`LeafIdx`]s of the runs containing the given [`Text`],
+    /// each paired with the part of the `Text` contained in it.
+    ///
+    /// The parts are sorted by their temporal offset which, since a `Text`
+    /// is always inserted as a contiguous block, is also their order in the
+    /// document.
+    #[inline]
+    fn parts_of_text<'a>(
+        &'a self,
+        text: &'a Text,
+    ) -> impl Iterator<Item = (LeafIdx<EditRun>, Text)> + 'a {
+        self.run_indices.leaves_of_text(text).into_iter().map(|leaf_idx| {
+            let run = self.gtree.leaf(leaf_idx);
+            let start = run.start().max(text.start());
+            let end = run.end().min(text.end());
+            (leaf_idx, Text::new(text.inserted_by(), start..end))
+        })
+    }
+
     #[inline]
     pub fn run(&self, run_idx: LeafIdx<EditRun>) -> &EditRun {
         self.gtree.leaf(run_idx)
@@ -431,11 +620,19 @@ impl RunTree {
         leaf_idx: LeafIdx<EditRun>,
         leaf_offset: Length,
         range: Range<Length>,
+        deleted_by: DeletedBy,
     ) -> LeafIdx<EditRun> {
         let run = self.gtree.leaf(leaf_idx);
 
         debug_assert!(!run.is_deleted);
 
+        let deleted = Text::new(
+            run.replica_id(),
+            run.start() + range.start..run.start() + range.end,
+        );
+
+        self.tombstones.insert(&deleted, deleted_by);
+
         let id_range = run.replica_id();
         let run_ts_range = run.run_ts();
         let deleted_range_offset = run.start();
@@ -505,6 +702,9 @@ impl RunTree {
         &mut self,
         deletion: &Deletion,
     ) -> Vec<ops::Range<usize>> {
+        let deleted_by =
+            DeletedBy::new(deletion.deleted_by(), deletion.deletion_ts());
+
         let start_idx = if deletion.start().is_zero() {
             // If the deletion starts at the beginning of the document we start
             // from the first run that was visible when the deletion was made.
@@ -538,7 +738,12 @@ impl RunTree {
 
                 let delete_up_to = deletion.end().offset() - run.start();
                 let delete_range = (delete_from..delete_up_to).into();
-                self.delete_leaf_range(start_idx, leaf_offset, delete_range);
+                self.delete_leaf_range(
+                    start_idx,
+                    leaf_offset,
+                    delete_range,
+                    deleted_by,
+                );
                 ranges.push((delete_range + leaf_offset).into());
             }
 
@@ -576,6 +781,7 @@ impl RunTree {
                     start_idx,
                     leaf_offset,
                     (delete_from..delete_up_to).into(),
+                    deleted_by,
                 );
 
                 let deletion_start = leaf_offset + delete_from;
@@ -594,6 +800,7 @@ impl RunTree {
                     start_idx,
                     leaf_offset,
                     (delete_from..len).into(),
+                    deleted_by,
                 );
 
                 leaf_offset += delete_from;
@@ -623,6 +830,7 @@ impl RunTree {
                         end_idx,
                         leaf_offset,
                         (0..delete_up_to).into(),
+                        deleted_by,
                     );
 
                     let deletion_start =
@@ -665,6 +873,7 @@ impl RunTree {
                         run_idx,
                         leaf_offset,
                         (0..delete_up_to).into(),
+                        deleted_by,
                     );
 
                     let deletion_start =
@@ -703,6 +912,8 @@ impl RunTree {
 
             gtree.with_leaf_mut(run_idx, EditRun::delete);
 
+            self.tombstones.insert(&run.text, deleted_by);
+
             gtree.remove_cursor();
 
             if !matches!(state, DeletionState::Deleting(_)) {
@@ -786,7 +997,7 @@ impl RunTree {
         let (gtree, idx) = Gtree::from_first_leaf(first_run);
         let mut run_indices = RunIndices::new();
         run_indices.get_mut(id).append(len, idx);
-        Self { gtree, run_indices }
+        Self { gtree, run_indices, tombstones: Tombstones::new() }
     }
 
     #[inline]
@@ -797,6 +1008,15 @@ impl RunTree {
             return self.len();
         }
 
+        // If the anchor points into a run that was removed by a compaction we
+        // resolve it to where that run used to be.
+        if let Some(next) =
+            self.run_indices.collected(anchor.inner(), anchor.bias())
+        {
+            return self
+                .resolve_anchor(BiasedAnchor::new(next, AnchorBias::Right));
+        }
+
         let anchor_idx =
             self.run_indices.idx_at_anchor(anchor.inner(), anchor.bias());
 
@@ -854,6 +1074,97 @@ impl RunTree {
 
         offset
     }
+
+    /// Returns the parts of the given [`Text`] that are currently visible,
+    /// together with their offset ranges in the document.
+    ///
+    /// The parts are sorted by offset, and parts that are contiguous both in
+    /// the document and in the `Text`'s temporal range are merged together.
+    #[inline]
+    pub fn visible_parts(
+        &self,
+        text: &Text,
+    ) -> Vec<(ops::Range<Length>, Text)> {
+        let mut parts = Vec::<(ops::Range<Length>, Text)>::new();
+
+        for (leaf_idx, part) in self.parts_of_text(text) {
+            if self.gtree.leaf(leaf_idx).is_deleted {
+                continue;
+            }
+
+            let offset = self.gtree.offset_of_leaf(leaf_idx) + part.start()
+                - self.gtree.leaf(leaf_idx).start();
+
+            let range = offset..offset + part.len();
+
+            if let Some((last_range, last_part)) = parts.last_mut() {
+                if last_range.end == range.start
+                    && last_part.end() == part.start()
+                {
+                    last_range.end = range.end;
+                    last_part.range.end = part.end();
+                    continue;
+                }
+            }
+
+            parts.push((range, part));
+        }
+
+        parts
+    }
+
+    /// Returns the [`Text`]s that are currently visible in the given offset
+    /// range, sorted by offset.
+    ///
+    /// Texts that are contiguous both in the document and in the temporal
+    /// range of the same replica are merged together.
+    #[inline]
+    pub fn visible_texts(&self, range: ops::Range<Length>) -> Vec<Text> {
+        let mut texts = Vec::<Text>::new();
+
+        if range.is_empty() {
+            return texts;
+        }
+
+        let (first_idx, mut leaf_offset) =
+            self.gtree.leaf_at_offset(range.start);
+
+        for (_, run) in self.gtree.leaves::<true>(first_idx) {
+            if leaf_offset >= range.end {
+                break;
+            }
+
+            let run_len = run.visible_len();
+
+            let start = range.start.max(leaf_offset) - leaf_offset;
+
+            let end = range.end.min(leaf_offset + run_len) - leaf_offset;
+
+            leaf_offset += run_len;
+
+            if start >= end {
+                continue;
+            }
+
+            let text = Text::new(
+                run.replica_id(),
+                run.start() + start..run.start() + end,
+            );
+
+            if let Some(last) = texts.last_mut() {
+                if last.inserted_by() == text.inserted_by()
+                    && last.end() == text.start()
+                {
+                    last.range.end = text.end();
+                    continue;
+                }
+            }
+
+            texts.push(text);
+        }
+
+        texts
+    }
 }
 
 /// TODO: docs
@@ -1033,18 +1344,7 @@ impl EditRun {
         run_ts: RunTs,
         lamport_ts: LamportTs,
     ) -> Self {
-        Self::new(text, run_ts, lamport_ts, false)
-    }
-
-    /// TODO: docs
-    #[inline(always)]
-    pub fn new(
-        text: Text,
-        run_ts: RunTs,
-        lamport_ts: LamportTs,
-        is_deleted: bool,
-    ) -> Self {
-        Self { text, run_ts, lamport_ts, is_deleted }
+        Self { text, run_ts, lamport_ts, is_deleted: false }
     }
 
     /// Returns the [`ReplicaId`] of the replica that inserted this run.
@@ -1132,6 +1432,7 @@ pub(crate) type DebugAsSelf<'a> =
 #[cfg(feature = "encode")]
 pub(crate) mod encode {
     use core::mem;
+    use std::collections::BTreeMap;
 
     use super::*;
     use crate::encode::{
@@ -1141,7 +1442,13 @@ pub(crate) mod encode {
         Encode,
         IntDecodeError,
     };
-    use crate::gtree::{encode::InodeDecodeError, Inode, InodeIdx, Lnode};
+    use crate::gtree::{
+        encode::InodeDecodeError,
+        Inode,
+        InodeIdx,
+        Leaf,
+        Lnode,
+    };
     use crate::run_indices::{Fragment, Fragments, ReplicaIndices};
 
     impl EditRun {
@@ -1178,6 +1485,8 @@ pub(crate) mod encode {
             }
 
             self.gtree.root_idx().encode(buf);
+
+            self.tombstones.encode(buf);
         }
     }
 
@@ -1226,6 +1535,7 @@ pub(crate) mod encode {
         lnodes: Vec<Lnode<EditRun>>,
         run_indices: RunIndices,
         replica_indices: Vec<(Fragments, Length)>,
+        collected: BTreeMap<Length, (Length, Anchor)>,
         fragments: Fragments,
         replica_id: ReplicaId,
         run_ts: RunTs,
@@ -1271,7 +1581,9 @@ pub(crate) mod encode {
 
             let gtree = Gtree::new(inodes, lnodes, root_idx);
 
-            let this = Self { gtree, run_indices };
+            let (tombstones, buf) = Tombstones::decode(buf)?;
+
+            let this = Self { gtree, run_indices, tombstones };
 
             Ok((this, buf))
         }
@@ -1298,10 +1610,22 @@ pub(crate) mod encode {
         #[inline(always)]
         fn encode(&self, buf: &mut Vec<u8>) {
             self.replica_id.encode(buf);
+
+            let collected = self.runs.collected_ranges();
+
+            (collected.len() as u64).encode(buf);
+
+            for (start, end, next) in collected {
+                start.encode(buf);
+                end.encode(buf);
+                next.encode(buf);
+            }
+
             (self.runs.len() as RunTs).encode(buf);
 
-            for (fragments, _) in self.runs.iter() {
-                RunFragments::new(fragments, self.gtree).encode(buf);
+            for (fragments, offset) in self.runs.iter() {
+                RunFragments::new(fragments, *offset, self.runs, self.gtree)
+                    .encode(buf);
             }
         }
     }
@@ -1320,6 +1644,16 @@ pub(crate) mod encode {
         ) -> Result<(Self::Value, &'buf [u8]), Self::Error> {
             let (replica_id, buf) = ReplicaId::decode(buf)?;
 
+            let (num_collected, mut buf) = u64::decode(buf)?;
+
+            for _ in 0..num_collected {
+                let (start, new_buf) = Length::decode(buf)?;
+                let (end, new_buf) = Length::decode(new_buf)?;
+                let (next, new_buf) = Anchor::decode(new_buf)?;
+                ctx.collected.insert(start, (end, next));
+                buf = new_buf;
+            }
+
             let (num_runs, mut buf) = RunTs::decode(buf)?;
 
             ctx.replica_id = replica_id;
@@ -1333,8 +1667,10 @@ pub(crate) mod encode {
 
             let indices = mem::take(&mut ctx.replica_indices);
 
+            let collected = mem::take(&mut ctx.collected);
+
             *ctx.run_indices.get_mut(replica_id) =
-                ReplicaIndices::new(indices);
+                ReplicaIndices::new(indices, collected);
 
             Ok(((), buf))
         }
@@ -1342,13 +1678,20 @@ pub(crate) mod encode {
 
     struct RunFragments<'a> {
         fragments: &'a Fragments,
+        temporal_offset: Length,
+        runs: &'a ReplicaIndices,
         gtree: &'a Gtree,
     }
 
     impl<'a> RunFragments<'a> {
         #[inline(always)]
-        fn new(fragments: &'a Fragments, gtree: &'a Gtree) -> Self {
-            Self { fragments, gtree }
+        fn new(
+            fragments: &'a Fragments,
+            temporal_offset: Length,
+            runs: &'a ReplicaIndices,
+            gtree: &'a Gtree,
+        ) -> Self {
+            Self { fragments, temporal_offset, runs, gtree }
         }
     }
 
@@ -1357,8 +1700,16 @@ pub(crate) mod encode {
         fn encode(&self, buf: &mut Vec<u8>) {
             (self.fragments.num_fragments() as u64).encode(buf);
 
+            let mut temporal_offset = self.temporal_offset;
+
             for fragment in self.fragments.iter() {
-                RunFragment::new(fragment.leaf_idx(), self.gtree).encode(buf);
+                let is_collected =
+                    self.runs.collected_at(temporal_offset).is_some();
+
+                RunFragment::new(fragment, is_collected, self.gtree)
+                    .encode(buf);
+
+                temporal_offset += fragment.len();
             }
         }
     }
@@ -1392,27 +1743,44 @@ pub(crate) mod encode {
     }
 
     struct RunFragment<'a> {
-        leaf_idx: LeafIdx<EditRun>,
+        fragment: &'a Fragment,
+        is_collected: bool,
         gtree: &'a Gtree,
     }
 
     impl<'a> RunFragment<'a> {
         #[inline(always)]
-        fn new(leaf_idx: LeafIdx<EditRun>, gtree: &'a Gtree) -> Self {
-            Self { leaf_idx, gtree }
+        fn new(
+            fragment: &'a Fragment,
+            is_collected: bool,
+            gtree: &'a Gtree,
+        ) -> Self {
+            Self { fragment, is_collected, gtree }
         }
     }
 
     impl Encode for RunFragment<'_> {
         #[inline(always)]
         fn encode(&self, buf: &mut Vec<u8>) {
-            let edit_run = self.gtree.leaf(self.leaf_idx);
+            let leaf_idx = self.fragment.leaf_idx();
+
+            // The fragments of the runs removed by a compaction don't have a
+            // corresponding leaf in the Gtree, so we only need their length
+            // and the index they point to.
+            if self.is_collected {
+                self.fragment.len().encode(buf);
+                leaf_idx.encode(buf);
+                return;
+            }
+
+            let edit_run = self.gtree.leaf(leaf_idx);
 
             edit_run.text.len().encode(buf);
             edit_run.lamport_ts.encode(buf);
             edit_run.is_deleted.encode(buf);
-            self.leaf_idx.encode(buf);
-            self.gtree.parent(self.leaf_idx).encode(buf);
+
+            leaf_idx.encode(buf);
+            self.gtree.parent(leaf_idx).encode(buf);
         }
     }
 
@@ -1428,7 +1796,23 @@ pub(crate) mod encode {
             buf: &'buf [u8],
             ctx: &mut Self::Ctx,
         ) -> Result<(Self::Value, &'buf [u8]), Self::Error> {
+            let temporal_start = ctx.temporal_offset;
+
+            let is_collected = ctx
+                .collected
+                .range(..=temporal_start)
+                .next_back()
+                .is_some_and(|(_, &(end, _))| temporal_start < end);
+
             let (len, buf) = Length::decode(buf)?;
+
+            if is_collected {
+                let (leaf_idx, buf) = LeafIdx::<EditRun>::decode(buf)?;
+                ctx.fragments.append(Fragment::new(len, leaf_idx));
+                ctx.temporal_offset += len;
+                return Ok(((), buf));
+            }
+
             let (lamport_ts, buf) = LamportTs::decode(buf)?;
             let (is_deleted, buf) = bool::decode(buf)?;
             let (leaf_idx, buf) = LeafIdx::<EditRun>::decode(buf)?;
@@ -1436,16 +1820,18 @@ pub(crate) mod encode {
 
             ctx.fragments.append(Fragment::new(len, leaf_idx));
 
-            let temporal_start = ctx.temporal_offset;
-
             ctx.temporal_offset += len;
 
             let temporal_end = ctx.temporal_offset;
 
             let text = Text::new(ctx.replica_id, temporal_start..temporal_end);
 
-            let edit_run =
-                EditRun::new(text, ctx.run_ts, lamport_ts, is_deleted);
+            let edit_run = EditRun {
+                text,
+                run_ts: ctx.run_ts,
+                lamport_ts,
+                is_deleted,
+            };
 
             ctx.lnodes[leaf_idx.into_usize()] =
                 Lnode::new(edit_run, parent_idx);
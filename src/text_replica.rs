@@ -0,0 +1,634 @@
+use alloc::borrow::Cow;
+use core::ops::{Range, RangeBounds};
+use std::collections::HashMap;
+
+use ropey::{Rope, RopeSlice};
+
+use crate::panic_messages as panic;
+use crate::*;
+
+/// A [`Replica`] which also owns the contents of the document it tracks.
+///
+/// A plain `Replica` doesn't know anything about the text it's replicating,
+/// which means that every application has to keep its own buffer in sync with
+/// it, apply the output of the `integrate_*` methods in the right order, and
+/// store the contents of the [`Insertion`]s that end up in the backlog until
+/// they can be merged.
+///
+/// A `TextReplica` does all of that for you. It stores the text in a
+/// [`Rope`], and every local edit produces a self-contained [`TextEdit`]
+/// which also carries the inserted string, so that it can be sent to and
+/// integrated by other `TextReplica`s with a single method call.
+///
+/// All offsets are UTF-8 byte offsets, and they must lie on `char`
+/// boundaries.
+///
+/// # Examples
+///
+/// ```
+/// # use cola::TextReplica;
+/// let mut peer_1 = TextReplica::new(1, "Hello, world");
+/// let mut peer_2 = peer_1.fork(2);
+///
+/// let delete_comma = peer_1.delete(5..6);
+/// let insert_exclamation = peer_2.insert(12, "!");
+///
+/// peer_1.integrate(&insert_exclamation);
+/// peer_2.integrate(&delete_comma);
+///
+/// assert_eq!(peer_1.contents(), "Hello world!");
+/// assert_eq!(peer_2.contents(), "Hello world!");
+/// ```
+#[cfg_attr(docsrs, doc(cfg(feature = "text-replica")))]
+#[derive(Clone)]
+pub struct TextReplica {
+    /// The CRDT tracking the edits made to `rope`.
+    replica: Replica,
+
+    /// The contents of the document.
+    rope: Rope,
+
+    /// The contents of the insertions in the replica's backlog, keyed by
+    /// their [`Text`].
+    backlog: HashMap<Text, String>,
+}
+
+/// An edit performed on a [`TextReplica`].
+///
+/// Unlike [`Insertion`]s, which only describe *where* some text was inserted,
+/// the insertion variant of this enum also carries the inserted string, so a
+/// `TextEdit` contains everything another [`TextReplica`] needs to integrate
+/// it.
+///
+/// This enum is created by the [`insert`](TextReplica::insert) and
+/// [`delete`](TextReplica::delete) methods on [`TextReplica`], and can be
+/// integrated by another [`TextReplica`] via the
+/// [`integrate`](TextReplica::integrate) method.
+#[cfg_attr(docsrs, doc(cfg(feature = "text-replica")))]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TextEdit {
+    /// An insertion, together with the text that was inserted.
+    Insertion(Insertion, String),
+
+    /// A deletion.
+    Deletion(Deletion),
+}
+
+impl TextReplica {
+    /// Returns the contents of the document.
+    ///
+    /// The text is stored in a [`Rope`], so this only borrows from it if the
+    /// whole document is stored in a single chunk. In all other cases the
+    /// chunks are first collected into a new `String`, which allocates and
+    /// copies the whole document. Use [`slice`](Self::slice) or
+    /// [`rope`](Self::rope) to avoid that.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use cola::TextReplica;
+    /// let mut replica = TextReplica::new(1, "foo");
+    /// let _ = replica.insert(3, "bar");
+    /// assert_eq!(replica.contents(), "foobar");
+    /// ```
+    #[inline]
+    pub fn contents(&self) -> Cow<'_, str> {
+        self.rope.slice(..).into()
+    }
+
+    /// Returns the char offset of the given byte offset.
+    #[track_caller]
+    #[inline]
+    fn byte_to_char(&self, byte_offset: Length) -> usize {
+        byte_to_char(&self.rope, byte_offset)
+    }
+
+    /// Returns the char range of the given byte range.
+    #[track_caller]
+    #[inline]
+    fn byte_range_to_char_range(
+        &self,
+        byte_range: Range<Length>,
+    ) -> Range<usize> {
+        if byte_range.start > byte_range.end {
+            panic::start_greater_than_end(byte_range.start, byte_range.end);
+        }
+
+        self.byte_to_char(byte_range.start)..self.byte_to_char(byte_range.end)
+    }
+
+    /// Deletes the text in the given byte range, returning the corresponding
+    /// [`TextEdit`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the start of the range is greater than the end, if the end
+    /// is out of bounds, or if either of them doesn't lie on a `char`
+    /// boundary.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use cola::TextReplica;
+    /// let mut replica = TextReplica::new(1, "Hello world");
+    /// let _ = replica.delete(..6);
+    /// assert_eq!(replica.contents(), "world");
+    /// ```
+    #[track_caller]
+    #[must_use]
+    #[inline]
+    pub fn delete<R>(&mut self, byte_range: R) -> TextEdit
+    where
+        R: RangeBounds<Length>,
+    {
+        let (start, end) =
+            range_bounds_to_start_end(byte_range, 0, self.len());
+        let char_range = self.byte_range_to_char_range(start..end);
+        self.rope.remove(char_range);
+        TextEdit::Deletion(self.replica.deleted(start..end))
+    }
+
+    /// Creates a new `TextReplica` with the given [`ReplicaId`] but with the
+    /// same contents and internal state as this one.
+    ///
+    /// See [`Replica::fork`] for more information.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the [`ReplicaId`] is zero or if it's equal to the id of this
+    /// `TextReplica`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use cola::TextReplica;
+    /// let replica1 = TextReplica::new(1, "foo");
+    /// let replica2 = replica1.fork(2);
+    /// assert_eq!(replica2.id(), 2);
+    /// assert_eq!(replica2.contents(), "foo");
+    /// ```
+    #[track_caller]
+    #[inline]
+    pub fn fork(&self, new_id: ReplicaId) -> Self {
+        Self {
+            replica: self.replica.fork(new_id),
+            rope: self.rope.clone(),
+            backlog: self.backlog.clone(),
+        }
+    }
+
+    /// Creates a new `TextReplica` from a [`Replica`] and the contents of the
+    /// document it tracks.
+    ///
+    /// This is how a new peer joining a session can build its `TextReplica`
+    /// after receiving the [`encode`](Replica::encode)d [`replica`] of an
+    /// existing peer together with the contents of its [`rope`].
+    ///
+    /// [`replica`]: Self::replica
+    /// [`rope`]: Self::rope
+    ///
+    /// # Panics
+    ///
+    /// Panics if the length of the `Replica` is not equal to the length of
+    /// the text in bytes, or if the `Replica` has some backlogged insertions,
+    /// since their contents are unknown.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use cola::{Replica, TextReplica};
+    /// let replica = Replica::new(1, 5);
+    /// let text_replica = TextReplica::from_parts(replica, "Hello");
+    /// assert_eq!(text_replica.contents(), "Hello");
+    /// ```
+    #[track_caller]
+    #[inline]
+    pub fn from_parts<T>(replica: Replica, text: T) -> Self
+    where
+        T: AsRef<str>,
+    {
+        let text = text.as_ref();
+
+        if replica.len() != text.len() {
+            panic::replica_len_mismatch(replica.len(), text.len());
+        }
+
+        if replica.backlog().has_insertions() {
+            panic::replica_has_backlogged_insertions();
+        }
+
+        let rope = Rope::from_str(text);
+        Self { replica, rope, backlog: HashMap::new() }
+    }
+
+    /// Returns the id of this `TextReplica`.
+    #[inline]
+    pub fn id(&self) -> ReplicaId {
+        self.replica.id()
+    }
+
+    /// Inserts the given string at the given byte offset, returning the
+    /// corresponding [`TextEdit`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the offset is out of bounds or if it doesn't lie on a `char`
+    /// boundary.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use cola::{TextEdit, TextReplica};
+    /// let mut replica = TextReplica::new(1, "ac");
+    ///
+    /// let TextEdit::Insertion(_, text) = replica.insert(1, "b") else {
+    ///     unreachable!();
+    /// };
+    ///
+    /// assert_eq!(text, "b");
+    /// assert_eq!(replica.contents(), "abc");
+    /// ```
+    #[track_caller]
+    #[must_use]
+    #[inline]
+    pub fn insert<T>(&mut self, byte_offset: Length, text: T) -> TextEdit
+    where
+        T: Into<String>,
+    {
+        let text = text.into();
+        let char_offset = self.byte_to_char(byte_offset);
+        self.rope.insert(char_offset, &text);
+        let insertion = self.replica.inserted(byte_offset, text.len());
+        TextEdit::Insertion(insertion, text)
+    }
+
+    /// Integrates a [`TextEdit`] created by another `TextReplica` into this
+    /// one, updating the contents of the document.
+    ///
+    /// If the edit depends on some other edit that this `TextReplica` hasn't
+    /// seen yet it's stored in a backlog, and it's automatically applied as
+    /// soon as the edits it depends on are integrated. Integrating an edit
+    /// more than once has no effect.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the edit is an insertion whose text is not as long as the
+    /// [`Insertion`], or if integrating it would split a `char` in two, which
+    /// can only happen if the edit wasn't created by a `TextReplica` forked
+    /// from this one.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use cola::TextReplica;
+    /// let mut peer_1 = TextReplica::new(1, "");
+    /// let mut peer_2 = peer_1.fork(2);
+    ///
+    /// let insert_a = peer_1.insert(0, "a");
+    /// let insert_b = peer_1.insert(1, "b");
+    ///
+    /// // The edits are received out of order, so the second one is
+    /// // backlogged..
+    /// peer_2.integrate(&insert_b);
+    /// assert_eq!(peer_2.contents(), "");
+    ///
+    /// // ..until the first one arrives.
+    /// peer_2.integrate(&insert_a);
+    /// assert_eq!(peer_2.contents(), "ab");
+    /// ```
+    #[track_caller]
+    #[inline]
+    pub fn integrate(&mut self, edit: &TextEdit) {
+        match edit {
+            TextEdit::Insertion(insertion, text) => {
+                self.integrate_insertion(insertion, text)
+            },
+            TextEdit::Deletion(deletion) => self.integrate_deletion(deletion),
+        }
+
+        self.integrate_backlog();
+    }
+
+    /// Applies all the backlogged edits that are now ready to be merged.
+    #[track_caller]
+    #[inline]
+    fn integrate_backlog(&mut self) {
+        loop {
+            let mut integrated_any = false;
+
+            // Insertions only depend on other insertions, while deletions
+            // can depend on both, so we keep going until neither iterator
+            // yields anything.
+
+            for (text, byte_offset) in self.replica.backlogged_insertions() {
+                let contents = self
+                    .backlog
+                    .remove(&text)
+                    .expect("the contents of backlogged insertions are saved");
+                insert_at_byte(&mut self.rope, byte_offset, &contents);
+                integrated_any = true;
+            }
+
+            for byte_ranges in self.replica.backlogged_deletions() {
+                delete_byte_ranges(&mut self.rope, byte_ranges);
+                integrated_any = true;
+            }
+
+            if !integrated_any {
+                break;
+            }
+        }
+    }
+
+    #[track_caller]
+    #[inline]
+    fn integrate_deletion(&mut self, deletion: &Deletion) {
+        let byte_ranges = self.replica.integrate_deletion(deletion);
+        delete_byte_ranges(&mut self.rope, byte_ranges);
+    }
+
+    #[track_caller]
+    #[inline]
+    fn integrate_insertion(&mut self, insertion: &Insertion, text: &str) {
+        if insertion.len() != text.len() {
+            panic::inserted_text_len_mismatch(insertion.len(), text.len());
+        }
+
+        if insertion.is_no_op()
+            || self.replica.has_merged_insertion(insertion)
+            || self.backlog.contains_key(insertion.text())
+        {
+            return;
+        }
+
+        match self.replica.integrate_insertion(insertion) {
+            Some(byte_offset) => {
+                insert_at_byte(&mut self.rope, byte_offset, text);
+            },
+            None => {
+                self.backlog.insert(insertion.text().clone(), text.to_owned());
+            },
+        }
+    }
+
+    /// Returns `true` if the document is empty.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the length of the document, in bytes.
+    #[inline]
+    pub fn len(&self) -> Length {
+        self.rope.len_bytes()
+    }
+
+    /// Creates a new `TextReplica` with the given [`ReplicaId`] and initial
+    /// contents.
+    ///
+    /// Just like with [`Replica::new`], this should only be called by the
+    /// peer that starts the collaboration session. All the other peers
+    /// should [`fork`](Self::fork) an existing `TextReplica` instead.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the [`ReplicaId`] is zero.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use cola::TextReplica;
+    /// let replica = TextReplica::new(1, "Hello");
+    /// assert_eq!(replica.len(), 5);
+    /// ```
+    #[track_caller]
+    #[inline]
+    pub fn new<T>(id: ReplicaId, text: T) -> Self
+    where
+        T: AsRef<str>,
+    {
+        let rope = Rope::from_str(text.as_ref());
+        let replica = Replica::new(id, rope.len_bytes());
+        Self { replica, rope, backlog: HashMap::new() }
+    }
+
+    /// Returns the underlying [`Replica`].
+    ///
+    /// This can be used to create and resolve [`Anchor`]s, or to
+    /// [`encode`](Replica::encode) the replica and send it to a new peer
+    /// together with the [`rope`](Self::rope)'s contents.
+    #[inline]
+    pub fn replica(&self) -> &Replica {
+        &self.replica
+    }
+
+    /// Returns the [`Rope`] storing the contents of the document.
+    #[inline]
+    pub fn rope(&self) -> &Rope {
+        &self.rope
+    }
+
+    /// Returns a slice of the document in the given byte range.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the start of the range is greater than the end, if the end
+    /// is out of bounds, or if either of them doesn't lie on a `char`
+    /// boundary.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use cola::TextReplica;
+    /// let replica = TextReplica::new(1, "Hello world");
+    /// assert_eq!(replica.slice(6..), "world");
+    /// ```
+    #[track_caller]
+    #[inline]
+    pub fn slice<R>(&self, byte_range: R) -> RopeSlice<'_>
+    where
+        R: RangeBounds<Length>,
+    {
+        let (start, end) =
+            range_bounds_to_start_end(byte_range, 0, self.len());
+        let char_range = self.byte_range_to_char_range(start..end);
+        self.rope.slice(char_range)
+    }
+}
+
+/// Returns the char offset of the given byte offset in the rope, panicking if
+/// it's out of bounds or if it doesn't lie on a `char` boundary.
+#[track_caller]
+#[inline]
+fn byte_to_char(rope: &Rope, byte_offset: Length) -> usize {
+    if byte_offset > rope.len_bytes() {
+        panic::offset_out_of_bounds(byte_offset, rope.len_bytes());
+    }
+
+    let char_offset = rope.byte_to_char(byte_offset);
+
+    if rope.char_to_byte(char_offset) != byte_offset {
+        panic::byte_offset_not_char_boundary(byte_offset);
+    }
+
+    char_offset
+}
+
+/// Deletes the given byte ranges from the rope, from last to first so that
+/// the offsets of the ranges that are yet to be deleted stay valid.
+#[track_caller]
+#[inline]
+fn delete_byte_ranges(rope: &mut Rope, byte_ranges: Vec<Range<Length>>) {
+    for byte_range in byte_ranges.into_iter().rev() {
+        let start = byte_to_char(rope, byte_range.start);
+        let end = byte_to_char(rope, byte_range.end);
+        rope.remove(start..end);
+    }
+}
+
+#[track_caller]
+#[inline]
+fn insert_at_byte(rope: &mut Rope, byte_offset: Length, text: &str) {
+    let char_offset = byte_to_char(rope, byte_offset);
+    rope.insert(char_offset, text);
+}
+
+impl core::fmt::Debug for TextReplica {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_struct("TextReplica")
+            .field("replica", &self.replica)
+            .field("rope", &self.rope)
+            .finish_non_exhaustive()
+    }
+}
+
+impl core::fmt::Display for TextReplica {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        core::fmt::Display::fmt(&self.rope, f)
+    }
+}
+
+#[cfg(feature = "encode")]
+mod encode {
+    use super::*;
+    use crate::deletion::encode::DeletionDecodeError;
+    use crate::encode::{BoolDecodeError, Decode, Encode, IntDecodeError};
+    use crate::insertion::encode::InsertionDecodeError;
+
+    impl Encode for TextEdit {
+        #[inline]
+        fn encode(&self, buf: &mut Vec<u8>) {
+            match self {
+                Self::Insertion(insertion, text) => {
+                    true.encode(buf);
+                    insertion.encode(buf);
+                    text.len().encode(buf);
+                    buf.extend_from_slice(text.as_bytes());
+                },
+                Self::Deletion(deletion) => {
+                    false.encode(buf);
+                    deletion.encode(buf);
+                },
+            }
+        }
+    }
+
+    pub(crate) enum TextEditDecodeError {
+        Bool(BoolDecodeError),
+        Deletion(DeletionDecodeError),
+        Insertion(InsertionDecodeError),
+        Int(IntDecodeError),
+        InvalidUtf8,
+        TextLenMismatch,
+        TextTooShort,
+    }
+
+    impl From<BoolDecodeError> for TextEditDecodeError {
+        #[inline(always)]
+        fn from(err: BoolDecodeError) -> Self {
+            Self::Bool(err)
+        }
+    }
+
+    impl From<DeletionDecodeError> for TextEditDecodeError {
+        #[inline(always)]
+        fn from(err: DeletionDecodeError) -> Self {
+            Self::Deletion(err)
+        }
+    }
+
+    impl From<InsertionDecodeError> for TextEditDecodeError {
+        #[inline(always)]
+        fn from(err: InsertionDecodeError) -> Self {
+            Self::Insertion(err)
+        }
+    }
+
+    impl From<IntDecodeError> for TextEditDecodeError {
+        #[inline(always)]
+        fn from(err: IntDecodeError) -> Self {
+            Self::Int(err)
+        }
+    }
+
+    impl core::fmt::Display for TextEditDecodeError {
+        #[inline]
+        fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+            let err: &dyn core::fmt::Display = match self {
+                Self::Bool(err) => err,
+                Self::Deletion(err) => err,
+                Self::Insertion(err) => err,
+                Self::Int(err) => err,
+                Self::InvalidUtf8 => &"the inserted text is not valid UTF-8",
+                Self::TextLenMismatch => {
+                    &"the inserted text is not as long as its insertion"
+                },
+                Self::TextTooShort => {
+                    &"the inserted text is shorter than its encoded length"
+                },
+            };
+
+            write!(f, "TextEdit couldn't be decoded: {err}")
+        }
+    }
+
+    impl Decode for TextEdit {
+        type Value = Self;
+
+        type Error = TextEditDecodeError;
+
+        #[inline]
+        fn decode(buf: &[u8]) -> Result<(Self, &[u8]), Self::Error> {
+            let (is_insertion, buf) = bool::decode(buf)?;
+
+            if !is_insertion {
+                let (deletion, buf) = Deletion::decode(buf)?;
+                return Ok((Self::Deletion(deletion), buf));
+            }
+
+            let (insertion, buf) = Insertion::decode(buf)?;
+            let (text_len, buf) = usize::decode(buf)?;
+
+            if text_len != insertion.len() {
+                return Err(TextEditDecodeError::TextLenMismatch);
+            }
+
+            if buf.len() < text_len {
+                return Err(TextEditDecodeError::TextTooShort);
+            }
+
+            let (text, buf) = buf.split_at(text_len);
+
+            let text = core::str::from_utf8(text)
+                .map_err(|_| TextEditDecodeError::InvalidUtf8)?;
+
+            Ok((Self::Insertion(insertion, text.to_owned()), buf))
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+mod serde {
+    crate::encode::impl_deserialize!(super::TextEdit);
+    crate::encode::impl_serialize!(super::TextEdit);
+}
@@ -0,0 +1,309 @@
+use std::collections::BTreeMap;
+
+use crate::*;
+
+/// Keeps track of which deletion deleted each deleted character in a
+/// [`RunTree`].
+///
+/// The deleted characters are identified by their temporal offsets which,
+/// unlike their position in the tree, never change. This allows
+/// [`EditRun`]s to be split and joined without having to care about who
+/// deleted them.
+#[derive(Clone, Default, PartialEq)]
+pub(crate) struct Tombstones {
+    /// Maps the [`ReplicaId`] of the replica that inserted some deleted
+    /// characters to the temporal ranges of those characters, keyed by their
+    /// start and mapped to their end and to the deletion that deleted them.
+    ///
+    /// The ranges of the same replica never overlap, and adjacent ranges
+    /// deleted by the same deletion are always joined together.
+    map: ReplicaIdMap<BTreeMap<Length, (Length, DeletedBy)>>,
+}
+
+/// The deletion that deleted some text.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) struct DeletedBy {
+    /// The [`ReplicaId`] of the replica that performed the deletion.
+    pub replica_id: ReplicaId,
+
+    /// The [`DeletionTs`] of the deletion.
+    pub deletion_ts: DeletionTs,
+}
+
+impl core::fmt::Debug for Tombstones {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        self.map.fmt(f)
+    }
+}
+
+impl DeletedBy {
+    #[inline]
+    pub fn new(replica_id: ReplicaId, deletion_ts: DeletionTs) -> Self {
+        Self { replica_id, deletion_ts }
+    }
+}
+
+impl Tombstones {
+    pub fn assert_invariants(&self) {
+        for ranges in self.map.values() {
+            let mut prev: Option<(Length, DeletedBy)> = None;
+
+            for (&start, &(end, deleted_by)) in ranges {
+                assert!(start < end);
+
+                if let Some((prev_end, prev_deleted_by)) = prev {
+                    assert!(prev_end <= start);
+                    assert!(prev_end < start || prev_deleted_by != deleted_by);
+                }
+
+                prev = Some((end, deleted_by));
+            }
+        }
+    }
+
+    /// Returns the deletions that deleted the given [`Text`], each paired
+    /// with the part of the `Text` it deleted, sorted by temporal offset.
+    ///
+    /// The parts of the `Text` that are not deleted are skipped.
+    #[inline]
+    pub fn deleted_by(&self, text: &Text) -> Vec<(Text, DeletedBy)> {
+        let Some(ranges) = self.map.get(&text.inserted_by()) else {
+            return Vec::new();
+        };
+
+        let first_start = ranges
+            .range(..=text.start())
+            .next_back()
+            .map(|(&start, _)| start)
+            .unwrap_or(text.start());
+
+        ranges
+            .range(first_start..text.end())
+            .filter(|(_, &(end, _))| end > text.start())
+            .map(|(&start, &(end, deleted_by))| {
+                let start = start.max(text.start());
+                let end = end.min(text.end());
+                (Text::new(text.inserted_by(), start..end), deleted_by)
+            })
+            .collect()
+    }
+
+    /// Records that the given [`Text`], which must not be already deleted,
+    /// was deleted by the given deletion.
+    #[inline]
+    pub fn insert(&mut self, text: &Text, deleted_by: DeletedBy) {
+        self.update(text, |old| {
+            debug_assert!(old.is_none());
+            Some(deleted_by)
+        });
+    }
+
+    /// Returns `true` if every character in the given [`Text`] is deleted,
+    /// and the deletions that deleted them are all included in the given
+    /// `DeletionMap`.
+    #[inline]
+    pub fn is_stable(&self, text: &Text, deletion_map: &DeletionMap) -> bool {
+        let mut covered_up_to = text.start();
+
+        for (part, deleted_by) in self.deleted_by(text) {
+            if part.start() != covered_up_to
+                || deletion_map.get(deleted_by.replica_id)
+                    < deleted_by.deletion_ts
+            {
+                return false;
+            }
+
+            covered_up_to = part.end();
+        }
+
+        covered_up_to == text.end()
+    }
+
+    #[cfg(feature = "encode")]
+    #[inline]
+    pub(crate) fn iter(
+        &self,
+    ) -> impl ExactSizeIterator<
+        Item = (&ReplicaId, &BTreeMap<Length, (Length, DeletedBy)>),
+    > + '_ {
+        self.map.iter()
+    }
+
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Forgets about the given [`Text`], which was removed from the
+    /// [`RunTree`] by a compaction.
+    #[inline]
+    pub fn remove(&mut self, text: &Text) {
+        self.update(text, |_| None);
+    }
+
+    /// Replaces who deleted each character in the given [`Text`] with the
+    /// value returned by `f`, which is called once for every part of the
+    /// `Text` whose characters currently map to the same value.
+    #[inline]
+    fn update<F>(&mut self, text: &Text, mut f: F)
+    where
+        F: FnMut(Option<DeletedBy>) -> Option<DeletedBy>,
+    {
+        if text.range.is_empty() {
+            return;
+        }
+
+        let ranges = self.map.entry(text.inserted_by()).or_default();
+
+        let (start, end) = (text.start(), text.end());
+
+        // Split the ranges that straddle the start or the end of the text so
+        // that every range is either fully inside or fully outside of it.
+        for at in [start, end] {
+            if let Some((&range_start, &(range_end, deleted_by))) =
+                ranges.range(..at).next_back()
+            {
+                if range_end > at {
+                    ranges.insert(range_start, (at, deleted_by));
+                    ranges.insert(at, (range_end, deleted_by));
+                }
+            }
+        }
+
+        let old = ranges
+            .range(start..end)
+            .map(|(&start, &(end, deleted_by))| (start, end, deleted_by))
+            .collect::<Vec<_>>();
+
+        let mut new = Vec::with_capacity(old.len() + 1);
+
+        let mut offset = start;
+
+        for (range_start, range_end, deleted_by) in old {
+            ranges.remove(&range_start);
+
+            if offset < range_start {
+                new.push((offset, range_start, f(None)));
+            }
+
+            new.push((range_start, range_end, f(Some(deleted_by))));
+
+            offset = range_end;
+        }
+
+        if offset < end {
+            new.push((offset, end, f(None)));
+        }
+
+        for (range_start, range_end, deleted_by) in new {
+            if let Some(deleted_by) = deleted_by {
+                ranges.insert(range_start, (range_end, deleted_by));
+            }
+        }
+
+        // Join the ranges that have become adjacent to a range deleted by
+        // the same deletion, starting from the one before the text.
+        let first = ranges
+            .range(..start)
+            .next_back()
+            .map(|(&start, _)| start)
+            .unwrap_or(start);
+
+        let keys = ranges
+            .range(first..=end)
+            .map(|(&start, _)| start)
+            .collect::<Vec<_>>();
+
+        let mut keys = keys.into_iter();
+
+        let Some(mut prev_start) = keys.next() else {
+            if ranges.is_empty() {
+                self.map.remove(&text.inserted_by());
+            }
+            return;
+        };
+
+        for start in keys {
+            let (prev_end, prev_deleted_by) = ranges[&prev_start];
+            let (end, deleted_by) = ranges[&start];
+
+            if prev_end == start && prev_deleted_by == deleted_by {
+                ranges.remove(&start);
+                ranges.insert(prev_start, (end, deleted_by));
+            } else {
+                prev_start = start;
+            }
+        }
+    }
+}
+
+#[cfg(feature = "encode")]
+pub(crate) mod encode {
+    use super::*;
+    use crate::encode::{Decode, Encode, IntDecodeError};
+
+    impl Encode for Tombstones {
+        #[inline]
+        fn encode(&self, buf: &mut Vec<u8>) {
+            (self.map.len() as u64).encode(buf);
+
+            for (&replica_id, ranges) in self.iter() {
+                replica_id.encode(buf);
+
+                (ranges.len() as u64).encode(buf);
+
+                let mut offset = 0;
+
+                // The ranges are sorted and don't overlap, so we only encode
+                // the distance from the end of the previous one.
+                for (&start, &(end, deleted_by)) in ranges {
+                    (start - offset).encode(buf);
+                    (end - start).encode(buf);
+                    deleted_by.replica_id.encode(buf);
+                    deleted_by.deletion_ts.encode(buf);
+                    offset = end;
+                }
+            }
+        }
+    }
+
+    impl Decode for Tombstones {
+        type Value = Self;
+
+        type Error = IntDecodeError;
+
+        #[inline]
+        fn decode(buf: &[u8]) -> Result<(Self, &[u8]), Self::Error> {
+            let (num_replicas, mut buf) = u64::decode(buf)?;
+
+            let mut this = Self::new();
+
+            for _ in 0..num_replicas {
+                let (replica_id, new_buf) = ReplicaId::decode(buf)?;
+                let (num_ranges, new_buf) = u64::decode(new_buf)?;
+                buf = new_buf;
+
+                let ranges = this.map.entry(replica_id).or_default();
+
+                let mut offset = 0;
+
+                for _ in 0..num_ranges {
+                    let (gap, new_buf) = Length::decode(buf)?;
+                    let (len, new_buf) = Length::decode(new_buf)?;
+                    let (deleter_id, new_buf) = ReplicaId::decode(new_buf)?;
+                    let (deletion_ts, new_buf) =
+                        DeletionTs::decode(new_buf)?;
+                    buf = new_buf;
+
+                    let start = offset + gap;
+                    let end = start + len;
+                    let deleted_by = DeletedBy::new(deleter_id, deletion_ts);
+                    ranges.insert(start, (end, deleted_by));
+                    offset = end;
+                }
+            }
+
+            Ok((this, buf))
+        }
+    }
+}
@@ -0,0 +1,508 @@
+use core::ops::{Range, RangeBounds};
+use std::collections::BTreeMap;
+
+use crate::*;
+
+/// Keeps track of the edits performed on a local [`Replica`] and turns them
+/// into new edits when they're undone or redone.
+///
+/// A CRDT can't simply "go back" to a previous state, since the other peers
+/// may have concurrently edited the document in the meantime. Instead, undoing
+/// an insertion deletes the parts of the inserted text that are still visible,
+/// and undoing a deletion re-inserts the deleted text where it used to be.
+/// The [`Insertion`]s and [`Deletion`]s produced in this way are regular
+/// edits which have to be sent to the other peers like any other edit, and
+/// concurrent edits performed by the other peers are left untouched.
+///
+/// Since the `UndoManager` needs to know which text was visible in a deleted
+/// range *before* it was deleted, local edits are recorded by calling
+/// [`inserted`](Self::inserted) and [`deleted`](Self::deleted) on the
+/// `UndoManager` instead of the methods with the same name on the `Replica`.
+///
+/// Edits are grouped together into undo units until
+/// [`end_group`](Self::end_group) is called, and all the edits in a group are
+/// undone and redone together.
+///
+/// # Examples
+///
+/// ```
+/// # use cola::{Replica, UndoEdit, UndoManager};
+/// let mut buffer = String::from("Hello");
+/// let mut replica = Replica::new(1, buffer.len());
+/// let mut undo_manager = UndoManager::new();
+///
+/// buffer.insert_str(5, " world");
+/// let _ = undo_manager.inserted(&mut replica, 5, 6);
+///
+/// for edit in undo_manager.undo(&mut replica) {
+///     if let UndoEdit::Deletion { range, .. } = edit {
+///         buffer.replace_range(range, "");
+///     }
+/// }
+///
+/// assert_eq!(buffer, "Hello");
+/// assert!(!undo_manager.can_undo());
+/// assert!(undo_manager.can_redo());
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct UndoManager {
+    /// The edits recorded since the last call to `end_group()`.
+    current_group: Vec<UndoEntry>,
+
+    /// The groups that can be undone, from the oldest to the most recent.
+    undo_stack: Vec<Vec<UndoEntry>>,
+
+    /// The groups that can be redone, from the oldest to the most recent.
+    redo_stack: Vec<Vec<UndoEntry>>,
+
+    /// Maps the `(inserted_by, start)` of every text that was re-inserted by
+    /// an undo or a redo to the new text it was re-inserted as.
+    ///
+    /// Since a character can only be deleted once, the ranges of these texts
+    /// never overlap.
+    ///
+    /// Entries are never removed since any text in the undo or redo stacks
+    /// could reach them, so this grows by one entry for every re-inserted
+    /// text until [`clear`](UndoManager::clear) is called.
+    restored: BTreeMap<(ReplicaId, Length), Text>,
+}
+
+/// A single edit recorded in an [`UndoManager`].
+#[derive(Clone, Debug)]
+enum UndoEntry {
+    /// The given text was inserted.
+    Inserted(Text),
+
+    /// The given texts, sorted by offset, were deleted.
+    Deleted(Vec<Text>),
+}
+
+/// An edit produced by [`UndoManager::undo`] or [`UndoManager::redo`].
+///
+/// Each variant contains both the CRDT edit to send to the other peers and
+/// the change to apply to the local buffer.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum UndoEdit {
+    /// Some previously deleted text was re-inserted.
+    Insertion {
+        /// The `Insertion` to send to the other peers.
+        insertion: Insertion,
+
+        /// The offset in the local buffer at which the text should be
+        /// inserted.
+        at_offset: Length,
+
+        /// The `Text` whose contents are being re-inserted. Its temporal
+        /// range has the same length as the one of the `Insertion`'s
+        /// [`text`](Insertion::text).
+        restores: Text,
+    },
+
+    /// Some previously inserted text was deleted.
+    Deletion {
+        /// The `Deletion` to send to the other peers.
+        deletion: Deletion,
+
+        /// The range of the local buffer that should be deleted.
+        range: Range<Length>,
+    },
+}
+
+impl UndoManager {
+    /// Returns `true` if there's at least one group of edits that can be
+    /// redone.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use cola::{Replica, UndoManager};
+    /// let mut replica = Replica::new(1, 0);
+    /// let mut undo_manager = UndoManager::new();
+    ///
+    /// let _ = undo_manager.inserted(&mut replica, 0, 1);
+    /// assert!(!undo_manager.can_redo());
+    ///
+    /// let _ = undo_manager.undo(&mut replica);
+    /// assert!(undo_manager.can_redo());
+    /// ```
+    #[inline]
+    pub fn can_redo(&self) -> bool {
+        !self.redo_stack.is_empty()
+    }
+
+    /// Returns `true` if there's at least one group of edits that can be
+    /// undone.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use cola::{Replica, UndoManager};
+    /// let mut replica = Replica::new(1, 0);
+    /// let mut undo_manager = UndoManager::new();
+    ///
+    /// assert!(!undo_manager.can_undo());
+    ///
+    /// let _ = undo_manager.inserted(&mut replica, 0, 1);
+    /// assert!(undo_manager.can_undo());
+    /// ```
+    #[inline]
+    pub fn can_undo(&self) -> bool {
+        !self.current_group.is_empty() || !self.undo_stack.is_empty()
+    }
+
+    /// Informs the `UndoManager` and the given `Replica` that the text in the
+    /// given offset range was deleted, returning the same [`Deletion`]
+    /// returned by [`Replica::deleted`].
+    ///
+    /// # Panics
+    ///
+    /// Panics under the same conditions as [`Replica::deleted`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use cola::{Replica, UndoManager};
+    /// let mut replica = Replica::new(1, 11);
+    /// let mut undo_manager = UndoManager::new();
+    ///
+    /// let _ = undo_manager.deleted(&mut replica, 5..);
+    ///
+    /// assert_eq!(replica.len(), 5);
+    /// assert!(undo_manager.can_undo());
+    /// ```
+    #[track_caller]
+    #[must_use]
+    #[inline]
+    pub fn deleted<R>(&mut self, replica: &mut Replica, range: R) -> Deletion
+    where
+        R: RangeBounds<Length>,
+    {
+        let (start, end) = range_bounds_to_start_end(range, 0, replica.len());
+
+        let texts = if start < end && end <= replica.len() {
+            replica.run_tree().visible_texts(start..end)
+        } else {
+            Vec::new()
+        };
+
+        let deletion = replica.deleted(start..end);
+
+        if !texts.is_empty() {
+            self.record(UndoEntry::Deleted(texts));
+        }
+
+        deletion
+    }
+
+    /// Forgets all the edits recorded so far, so that there's nothing left to
+    /// undo or redo.
+    ///
+    /// The `UndoManager` keeps some bookkeeping for every text re-inserted by
+    /// an undo or a redo for as long as it has a history, so long editing
+    /// sessions may want to call this every now and then (e.g. when the
+    /// document is saved) to free that memory.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use cola::{Replica, UndoManager};
+    /// let mut replica = Replica::new(1, 0);
+    /// let mut undo_manager = UndoManager::new();
+    ///
+    /// let _ = undo_manager.inserted(&mut replica, 0, 1);
+    /// undo_manager.clear();
+    ///
+    /// assert!(!undo_manager.can_undo());
+    /// assert!(undo_manager.undo(&mut replica).is_empty());
+    /// ```
+    #[inline]
+    pub fn clear(&mut self) {
+        self.current_group.clear();
+        self.undo_stack.clear();
+        self.redo_stack.clear();
+        self.restored.clear();
+    }
+
+    /// Closes the current group of edits, so that the edits recorded after
+    /// this call will be undone separately from the ones recorded before it.
+    ///
+    /// This has no effect if no edits have been recorded since the last call
+    /// to this method.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use cola::{Replica, UndoManager};
+    /// let mut replica = Replica::new(1, 0);
+    /// let mut undo_manager = UndoManager::new();
+    ///
+    /// let _ = undo_manager.inserted(&mut replica, 0, 1);
+    /// undo_manager.end_group();
+    /// let _ = undo_manager.inserted(&mut replica, 1, 1);
+    ///
+    /// // Only the second insertion is undone.
+    /// assert_eq!(undo_manager.undo(&mut replica).len(), 1);
+    /// assert_eq!(replica.len(), 1);
+    /// ```
+    #[inline]
+    pub fn end_group(&mut self) {
+        if !self.current_group.is_empty() {
+            let group = core::mem::take(&mut self.current_group);
+            self.undo_stack.push(group);
+        }
+    }
+
+    /// Informs the `UndoManager` and the given `Replica` that some text of
+    /// the given length was inserted at the given offset, returning the same
+    /// [`Insertion`] returned by [`Replica::inserted`].
+    ///
+    /// # Panics
+    ///
+    /// Panics under the same conditions as [`Replica::inserted`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use cola::{Replica, UndoManager};
+    /// let mut replica = Replica::new(1, 0);
+    /// let mut undo_manager = UndoManager::new();
+    ///
+    /// let insertion = undo_manager.inserted(&mut replica, 0, 5);
+    ///
+    /// assert_eq!(insertion.text().temporal_range(), 0..5);
+    /// assert!(undo_manager.can_undo());
+    /// ```
+    #[track_caller]
+    #[must_use]
+    #[inline]
+    pub fn inserted(
+        &mut self,
+        replica: &mut Replica,
+        at_offset: Length,
+        len: Length,
+    ) -> Insertion {
+        let insertion = replica.inserted(at_offset, len);
+
+        if !insertion.is_no_op() {
+            self.record(UndoEntry::Inserted(insertion.text().clone()));
+        }
+
+        insertion
+    }
+
+    /// Creates a new, empty `UndoManager`.
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records a new local edit, clearing the redo stack.
+    #[inline]
+    fn record(&mut self, entry: UndoEntry) {
+        self.redo_stack.clear();
+        self.current_group.push(entry);
+    }
+
+    /// Redoes the most recently undone group of edits, returning the edits
+    /// that have to be sent to the other peers and applied to the local
+    /// buffer.
+    ///
+    /// The edits have to be applied to the local buffer in the same order as
+    /// they're returned, since the offsets of each edit take into account the
+    /// edits that come before it.
+    ///
+    /// If there's nothing to redo this returns an empty vector.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use cola::{Replica, UndoManager};
+    /// let mut replica = Replica::new(1, 0);
+    /// let mut undo_manager = UndoManager::new();
+    ///
+    /// let _ = undo_manager.inserted(&mut replica, 0, 5);
+    /// let _ = undo_manager.undo(&mut replica);
+    /// assert_eq!(replica.len(), 0);
+    ///
+    /// let _ = undo_manager.redo(&mut replica);
+    /// assert_eq!(replica.len(), 5);
+    /// ```
+    #[must_use]
+    #[inline]
+    pub fn redo(&mut self, replica: &mut Replica) -> Vec<UndoEdit> {
+        self.end_group();
+
+        let Some(group) = self.redo_stack.pop() else {
+            return Vec::new();
+        };
+
+        let (edits, inverse) = self.revert(group, replica);
+
+        if !inverse.is_empty() {
+            self.undo_stack.push(inverse);
+        }
+
+        edits
+    }
+
+    /// Reverts the given group of edits, returning the edits performed on the
+    /// `Replica` and the group that reverts them.
+    #[inline]
+    fn revert(
+        &mut self,
+        group: Vec<UndoEntry>,
+        replica: &mut Replica,
+    ) -> (Vec<UndoEdit>, Vec<UndoEntry>) {
+        let mut edits = Vec::new();
+
+        let mut inverse = Vec::new();
+
+        for entry in group.into_iter().rev() {
+            match entry {
+                UndoEntry::Inserted(text) => {
+                    let mut parts = Vec::new();
+
+                    self.visible_parts(&text, replica, &mut parts);
+
+                    if parts.is_empty() {
+                        continue;
+                    }
+
+                    parts.sort_by_key(|(range, _)| range.start);
+
+                    let mut texts = Vec::with_capacity(parts.len());
+
+                    // Delete the parts from last to first so that the offsets
+                    // of the remaining parts are not affected.
+                    for (range, text) in parts.into_iter().rev() {
+                        let deletion = replica.deleted(range.clone());
+                        edits.push(UndoEdit::Deletion { deletion, range });
+                        texts.push(text);
+                    }
+
+                    texts.reverse();
+
+                    inverse.push(UndoEntry::Deleted(texts));
+                },
+
+                UndoEntry::Deleted(texts) => {
+                    for text in texts {
+                        // Each part is re-inserted right after its tombstone
+                        // instead of at its visible offset, which could be
+                        // shared with some concurrently inserted text.
+                        for (anchor, restores) in
+                            replica.run_tree().deleted_parts(&text)
+                        {
+                            let (insertion, at_offset) =
+                                replica.inserted_after(anchor, restores.len());
+
+                            let restored_as = insertion.text().clone();
+
+                            self.restored.insert(
+                                (restores.inserted_by(), restores.start()),
+                                restored_as.clone(),
+                            );
+
+                            inverse.push(UndoEntry::Inserted(restored_as));
+
+                            edits.push(UndoEdit::Insertion {
+                                insertion,
+                                at_offset,
+                                restores,
+                            });
+                        }
+                    }
+                },
+            }
+        }
+
+        // The inverse entries were produced from the last edit to the first,
+        // so they're already in the order in which they've been performed.
+        (edits, inverse)
+    }
+
+    /// Undoes the most recent group of edits, returning the edits that have
+    /// to be sent to the other peers and applied to the local buffer.
+    ///
+    /// The edits have to be applied to the local buffer in the same order as
+    /// they're returned, since the offsets of each edit take into account the
+    /// edits that come before it.
+    ///
+    /// If there's nothing to undo this returns an empty vector.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use cola::{Replica, UndoEdit, UndoManager};
+    /// let mut buffer = String::from("Hello world");
+    /// let mut replica = Replica::new(1, buffer.len());
+    /// let mut undo_manager = UndoManager::new();
+    ///
+    /// buffer.replace_range(5.., "");
+    /// let _ = undo_manager.deleted(&mut replica, 5..);
+    ///
+    /// for edit in undo_manager.undo(&mut replica) {
+    ///     if let UndoEdit::Insertion { at_offset, restores, .. } = edit {
+    ///         // Here we'd look up the contents of the `restores` text. Since
+    ///         // the initial text of the document was inserted by peer 1, its
+    ///         // temporal range matches the offsets in the original buffer.
+    ///         assert_eq!(restores.temporal_range(), 5..11);
+    ///         buffer.insert_str(at_offset, " world");
+    ///     }
+    /// }
+    ///
+    /// assert_eq!(buffer, "Hello world");
+    /// assert_eq!(replica.len(), 11);
+    /// ```
+    #[must_use]
+    #[inline]
+    pub fn undo(&mut self, replica: &mut Replica) -> Vec<UndoEdit> {
+        self.end_group();
+
+        let Some(group) = self.undo_stack.pop() else {
+            return Vec::new();
+        };
+
+        let (edits, inverse) = self.revert(group, replica);
+
+        if !inverse.is_empty() {
+            self.redo_stack.push(inverse);
+        }
+
+        edits
+    }
+
+    /// Pushes the parts of the given text that are currently visible onto
+    /// `parts`, including the ones that were deleted and then re-inserted as
+    /// a different text by an undo or a redo.
+    #[inline]
+    fn visible_parts(
+        &self,
+        text: &Text,
+        replica: &Replica,
+        parts: &mut Vec<(Range<Length>, Text)>,
+    ) {
+        parts.extend(replica.run_tree().visible_parts(text));
+
+        let id = text.inserted_by();
+
+        for (&(_, start), restored_as) in
+            self.restored.range((id, 0)..(id, text.end())).rev()
+        {
+            let end = start + restored_as.len();
+
+            if end <= text.start() {
+                break;
+            }
+
+            let offset = restored_as.start() - start;
+
+            let restored_part = Text::new(
+                restored_as.inserted_by(),
+                start.max(text.start()) + offset..end.min(text.end()) + offset,
+            );
+
+            self.visible_parts(&restored_part, replica, parts);
+        }
+    }
+}
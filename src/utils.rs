@@ -128,6 +128,29 @@ where
 pub mod panic_messages {
     use crate::Length;
 
+    #[cfg(feature = "text-replica")]
+    #[track_caller]
+    #[cold]
+    #[inline(never)]
+    pub(crate) fn byte_offset_not_char_boundary(offset: Length) -> ! {
+        panic!("byte offset {offset} is not a char boundary");
+    }
+
+    #[cfg(feature = "text-replica")]
+    #[track_caller]
+    #[cold]
+    #[inline(never)]
+    pub(crate) fn inserted_text_len_mismatch(
+        insertion_len: Length,
+        text_len: Length,
+    ) -> ! {
+        debug_assert!(insertion_len != text_len);
+        panic!(
+            "inserted text doesn't match its insertion: the insertion's \
+             length is {insertion_len} but the text's length is {text_len}"
+        );
+    }
+
     #[track_caller]
     #[cold]
     #[inline(never)]
@@ -139,6 +162,32 @@ pub mod panic_messages {
         );
     }
 
+    #[cfg(feature = "text-replica")]
+    #[track_caller]
+    #[cold]
+    #[inline(never)]
+    pub(crate) fn replica_has_backlogged_insertions() -> ! {
+        panic!(
+            "the replica has some backlogged insertions whose contents are \
+             unknown"
+        );
+    }
+
+    #[cfg(feature = "text-replica")]
+    #[track_caller]
+    #[cold]
+    #[inline(never)]
+    pub(crate) fn replica_len_mismatch(
+        replica_len: Length,
+        text_len: Length,
+    ) -> ! {
+        debug_assert!(replica_len != text_len);
+        panic!(
+            "the replica's length is {replica_len} but the text's length is \
+             {text_len}"
+        );
+    }
+
     #[track_caller]
     #[cold]
     #[inline(never)]
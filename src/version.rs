@@ -0,0 +1,210 @@
+use core::ops::Range;
+
+use crate::*;
+
+/// A snapshot of the edits a [`Replica`] has integrated, obtained by calling
+/// [`Replica::version`].
+///
+/// `Version`s are meant to be exchanged between peers. For example, when two
+/// peers reconnect after a network partition they can send each other their
+/// `Version`, and each peer can then call [`Replica::missing_since`] to find
+/// out which of its edits the other peer is missing and only send those,
+/// instead of sending a whole [`EncodedReplica`](crate::EncodedReplica).
+///
+/// The [`meet`](Self::meet) of the `Version`s of all the peers in a session
+/// can also be used as the causally stable frontier passed to
+/// [`Replica::compact`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Version {
+    /// The character timestamps of all the insertions included in this
+    /// version.
+    version_map: VersionMap,
+
+    /// The deletion timestamps of all the deletions included in this version.
+    deletion_map: DeletionMap,
+
+    /// The highest Lamport timestamp of the edits included in this version.
+    lamport_ts: LamportTs,
+}
+
+impl Version {
+    #[inline]
+    pub(crate) fn deletion_map(&self) -> &DeletionMap {
+        &self.deletion_map
+    }
+
+    /// Returns the `Version` containing only the edits included in both this
+    /// `Version` and the given one.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use cola::Replica;
+    /// let mut peer_1 = Replica::new(1, 10);
+    /// let peer_2 = peer_1.fork(2);
+    ///
+    /// let _ = peer_1.deleted(..5);
+    ///
+    /// // Peer 2 hasn't integrated the deletion, so compacting peer 1 against
+    /// // the meet of the two versions doesn't remove the deleted text.
+    /// let frontier = peer_1.version().meet(&peer_2.version());
+    /// assert_eq!(peer_1.compact(&frontier).removed_runs(), 0);
+    /// ```
+    #[inline]
+    pub fn meet(&self, other: &Self) -> Self {
+        Self {
+            version_map: self.version_map.meet(&other.version_map),
+            deletion_map: self.deletion_map.meet(&other.deletion_map),
+            lamport_ts: self.lamport_ts.min(other.lamport_ts),
+        }
+    }
+
+    #[inline]
+    pub(crate) fn new(
+        version_map: VersionMap,
+        deletion_map: DeletionMap,
+        lamport_ts: LamportTs,
+    ) -> Self {
+        Self { version_map, deletion_map, lamport_ts }
+    }
+
+    #[inline]
+    pub(crate) fn version_map(&self) -> &VersionMap {
+        &self.version_map
+    }
+}
+
+/// The edits integrated by a [`Replica`] that are not included in a given
+/// [`Version`], returned by [`Replica::missing_since`].
+///
+/// The edits are identified by the same coordinates used by [`Insertion`]s
+/// and [`Deletion`]s: insertions by the [`Text`] they inserted, and
+/// deletions by the [`ReplicaId`] of the peer that performed them together
+/// with their [`deletion_ts`](Deletion::deletion_ts).
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MissingEdits {
+    /// The texts inserted since the version, sorted by `ReplicaId`.
+    insertions: Vec<Text>,
+
+    /// The deletions performed since the version, sorted by `ReplicaId`.
+    deletions: Vec<(ReplicaId, Range<DeletionTs>)>,
+}
+
+impl MissingEdits {
+    /// Returns the ranges of deletion timestamps of the [`Deletion`]s that
+    /// are missing, grouped by the [`ReplicaId`] of the peer that performed
+    /// them and sorted by it.
+    ///
+    /// A [`Deletion`] is missing if its
+    /// [`deleted_by`](Deletion::deleted_by) matches one of the returned
+    /// `ReplicaId`s and its [`deletion_ts`](Deletion::deletion_ts) falls in
+    /// the corresponding range.
+    #[inline]
+    pub fn deletions(&self) -> &[(ReplicaId, Range<DeletionTs>)] {
+        &self.deletions
+    }
+
+    /// Returns the [`Text`]s inserted by the [`Insertion`]s that are missing,
+    /// one per peer, sorted by the [`ReplicaId`] of the peer that inserted
+    /// them.
+    ///
+    /// An [`Insertion`] is missing if its [`text`](Insertion::text) is
+    /// contained in one of the returned `Text`s.
+    #[inline]
+    pub fn insertions(&self) -> &[Text] {
+        &self.insertions
+    }
+
+    /// Returns `true` if there are no missing edits.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.insertions.is_empty() && self.deletions.is_empty()
+    }
+
+    #[inline]
+    pub(crate) fn new(
+        mut insertions: Vec<Text>,
+        mut deletions: Vec<(ReplicaId, Range<DeletionTs>)>,
+    ) -> Self {
+        insertions.sort_unstable_by_key(Text::inserted_by);
+        deletions.sort_unstable_by_key(|&(replica_id, _)| replica_id);
+        Self { insertions, deletions }
+    }
+}
+
+#[cfg(feature = "encode")]
+mod encode {
+    use super::*;
+    use crate::encode::{Decode, Encode, IntDecodeError};
+    use crate::version_map::encode::BaseMapDecodeError;
+
+    impl Encode for Version {
+        #[inline]
+        fn encode(&self, buf: &mut Vec<u8>) {
+            self.version_map.encode(buf);
+            self.deletion_map.encode(buf);
+            self.lamport_ts.encode(buf);
+        }
+    }
+
+    pub(crate) enum VersionDecodeError {
+        DeletionMap(BaseMapDecodeError<DeletionTs>),
+        Int(IntDecodeError),
+        VersionMap(BaseMapDecodeError<Length>),
+    }
+
+    impl From<BaseMapDecodeError<DeletionTs>> for VersionDecodeError {
+        #[inline(always)]
+        fn from(err: BaseMapDecodeError<DeletionTs>) -> Self {
+            Self::DeletionMap(err)
+        }
+    }
+
+    impl From<IntDecodeError> for VersionDecodeError {
+        #[inline(always)]
+        fn from(err: IntDecodeError) -> Self {
+            Self::Int(err)
+        }
+    }
+
+    impl From<BaseMapDecodeError<Length>> for VersionDecodeError {
+        #[inline(always)]
+        fn from(err: BaseMapDecodeError<Length>) -> Self {
+            Self::VersionMap(err)
+        }
+    }
+
+    impl core::fmt::Display for VersionDecodeError {
+        #[inline]
+        fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+            let err: &dyn core::fmt::Display = match self {
+                Self::DeletionMap(err) => err,
+                Self::Int(err) => err,
+                Self::VersionMap(err) => err,
+            };
+
+            write!(f, "Version couldn't be decoded: {err}")
+        }
+    }
+
+    impl Decode for Version {
+        type Value = Self;
+
+        type Error = VersionDecodeError;
+
+        #[inline]
+        fn decode(buf: &[u8]) -> Result<(Self, &[u8]), Self::Error> {
+            let (version_map, buf) = VersionMap::decode(buf)?;
+            let (deletion_map, buf) = DeletionMap::decode(buf)?;
+            let (lamport_ts, buf) = LamportTs::decode(buf)?;
+            let this = Self::new(version_map, deletion_map, lamport_ts);
+            Ok((this, buf))
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+mod serde {
+    crate::encode::impl_serialize!(super::Version);
+    crate::encode::impl_deserialize!(super::Version);
+}
@@ -68,7 +68,7 @@ impl<T: Copy> BaseMap<T> {
     }
 
     #[inline]
-    fn iter(&self) -> impl Iterator<Item = (ReplicaId, T)> + '_ {
+    pub fn iter(&self) -> impl Iterator<Item = (ReplicaId, T)> + '_ {
         let this_entry = core::iter::once((self.this_id, self.this_value));
         this_entry.chain(self.rest.iter().map(|(&id, &value)| (id, value)))
     }
@@ -82,6 +82,24 @@ impl<T: Copy> BaseMap<T> {
         this_entry.chain(self.rest.iter_mut().map(|(&id, value)| (id, value)))
     }
 
+    /// Returns the map containing, for every `ReplicaId`, the smallest of the
+    /// values associated to it in this map and in the given one.
+    #[inline]
+    pub fn meet(&self, other: &Self) -> Self
+    where
+        T: Default + Ord,
+    {
+        let this_value = self.this_value.min(other.get(self.this_id));
+
+        let mut meet = Self::new(self.this_id, this_value);
+
+        for (&replica_id, &value) in self.rest.iter() {
+            meet.rest.insert(replica_id, value.min(other.get(replica_id)));
+        }
+
+        meet
+    }
+
     #[inline]
     pub fn new(this_id: ReplicaId, this_value: T) -> Self {
         Self { this_id, this_value, rest: ReplicaIdMap::default() }
@@ -0,0 +1,245 @@
+mod common;
+
+use cola::{AnchorBias, ReplicaId, Version};
+use common::Replica;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+fn frontier(replicas: &[&Replica]) -> Version {
+    replicas
+        .iter()
+        .map(|replica| replica.crdt.version())
+        .reduce(|meet, version| meet.meet(&version))
+        .unwrap()
+}
+
+/// Tests that a deletion integrated by every peer is removed.
+#[test]
+fn compact_removes_stable_deletion() {
+    let mut replica1 = Replica::new(1, "Hello world");
+    let mut replica2 = replica1.fork(2);
+
+    let deletion = replica1.delete(0..6);
+    replica2.merge(&deletion);
+
+    assert_eq!(replica1.crdt.num_runs(), 2);
+
+    let frontier = frontier(&[&replica1, &replica2]);
+
+    let compaction = replica1.crdt.compact(&frontier);
+
+    assert_eq!(compaction.removed_runs(), 1);
+    assert_eq!(replica1.crdt.num_runs(), 1);
+    assert_eq!(replica1, "world");
+    replica1.assert_invariants();
+}
+
+/// Tests that a deletion that hasn't been integrated by every peer is not
+/// removed.
+#[test]
+fn compact_keeps_unstable_deletion() {
+    let mut replica1 = Replica::new(1, "abcdef");
+    let replica2 = replica1.fork(2);
+
+    let _ = replica1.delete(1..3);
+
+    let frontier = frontier(&[&replica1, &replica2]);
+
+    assert_eq!(replica1.crdt.compact(&frontier).removed_runs(), 0);
+    assert_eq!(replica1.crdt.num_runs(), 3);
+}
+
+/// Tests that the runs on both sides of a removed deletion are merged back
+/// together if they can be.
+#[test]
+fn compact_merges_runs() {
+    let mut replica1 = Replica::new(1, "abcdef");
+    let mut replica2 = replica1.fork(2);
+
+    let insertion = replica2.insert(3, "X");
+    replica1.merge(&insertion);
+
+    let deletion = replica1.delete(3..4);
+    replica2.merge(&deletion);
+
+    assert_eq!(replica1.crdt.num_runs(), 3);
+
+    let frontier = frontier(&[&replica1, &replica2]);
+
+    let compaction = replica1.crdt.compact(&frontier);
+
+    assert_eq!(compaction.removed_runs(), 1);
+    assert_eq!(compaction.merged_runs(), 1);
+    assert_eq!(compaction.reclaimed_runs(), 2);
+    assert_eq!(replica1.crdt.num_runs(), 1);
+    assert_eq!(replica1, "abcdef");
+    replica1.assert_invariants();
+}
+
+/// Tests that adjacent pieces of text deleted by different peers are joined
+/// into a single run, and that the run can be removed as soon as both
+/// deletions are causally stable, even if some other deletion isn't.
+#[test]
+fn compact_deletions_by_different_peers() {
+    let mut replica1 = Replica::new(1, "abcdefgh");
+    let mut replica2 = replica1.fork(2);
+
+    let del_1 = replica1.delete(1..2);
+    let del_2 = replica2.delete(2..3);
+
+    replica1.merge(&del_2);
+    replica2.merge(&del_1);
+
+    assert_eq!(replica1, "adefgh");
+    assert_eq!(replica1.crdt.num_runs(), 3);
+    assert_eq!(replica2.crdt.num_runs(), 3);
+
+    let del_3 = replica1.delete(4..5);
+
+    // Peer 2 hasn't integrated the last deletion, so only the "bc" run can
+    // be removed.
+    let stable = frontier(&[&replica1, &replica2]);
+    assert_eq!(replica1.crdt.compact(&stable).removed_runs(), 1);
+    assert_eq!(replica2.crdt.compact(&stable).removed_runs(), 1);
+
+    replica2.merge(&del_3);
+
+    let stable = frontier(&[&replica1, &replica2]);
+    assert_eq!(replica1.crdt.compact(&stable).removed_runs(), 1);
+    assert_eq!(replica2.crdt.compact(&stable).removed_runs(), 1);
+
+    assert_eq!(replica1, "adefh");
+    assert_eq!(replica2, "adefh");
+    replica1.assert_invariants();
+    replica2.assert_invariants();
+}
+
+/// Tests that anchors pointing into removed text resolve to the offset where
+/// that text used to be.
+#[test]
+fn compact_anchors() {
+    let mut replica1 = Replica::new(1, "abcdef");
+    let mut replica2 = replica1.fork(2);
+
+    let left = replica1.crdt.create_anchor(3, AnchorBias::Left);
+    let right = replica1.crdt.create_anchor(3, AnchorBias::Right);
+
+    let deletion = replica1.delete(2..5);
+    replica2.merge(&deletion);
+
+    let frontier = frontier(&[&replica1, &replica2]);
+
+    assert_eq!(replica1.crdt.compact(&frontier).removed_runs(), 1);
+
+    assert_eq!(replica1.crdt.resolve_anchor(left), Some(2));
+    assert_eq!(replica1.crdt.resolve_anchor(right), Some(2));
+
+    let insertion = replica1.insert(0, "xy");
+    replica2.merge(&insertion);
+
+    assert_eq!(replica1.crdt.resolve_anchor(left), Some(4));
+    assert_eq!(replica1.crdt.resolve_anchor(right), Some(4));
+}
+
+/// Tests that peers keep converging when only some of them have compacted
+/// their `Replica`s.
+#[test]
+fn compact_then_concurrent_edits() {
+    let mut replica1 = Replica::new(1, "abcdefgh");
+    let mut replica2 = replica1.fork(2);
+    let mut replica3 = replica1.fork(3);
+
+    let deletion = replica1.delete(2..6);
+    replica2.merge(&deletion);
+    replica3.merge(&deletion);
+
+    let frontier = frontier(&[&replica1, &replica2, &replica3]);
+
+    assert_eq!(replica1.crdt.compact(&frontier).removed_runs(), 1);
+    assert_eq!(replica2.crdt.compact(&frontier).removed_runs(), 1);
+
+    let ins_1 = replica1.insert(2, "123");
+    let ins_2 = replica2.insert(2, "45");
+    let del_3 = replica3.delete(1..3);
+
+    replica1.merge(&ins_2);
+    replica1.merge(&del_3);
+
+    replica2.merge(&del_3);
+    replica2.merge(&ins_1);
+
+    replica3.merge(&ins_1);
+    replica3.merge(&ins_2);
+
+    assert_convergence!(replica1, replica2, replica3, "a12345h");
+}
+
+#[test]
+fn compact_random() {
+    let seed = rand::random::<u64>();
+    println!("seed: {seed}");
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+    test_compact_random(&mut rng, 4, 40, 10);
+}
+
+fn test_compact_random(
+    rng: &mut impl Rng,
+    num_replicas: usize,
+    num_cycles: usize,
+    edits_per_cycle: usize,
+) {
+    let first = Replica::new_with_len(1, 32, rng);
+
+    let mut replicas = vec![first];
+
+    for i in 1..num_replicas {
+        replicas.push(replicas[0].fork(i as ReplicaId + 1));
+    }
+
+    // The meet of the versions of all the replicas at the end of the last
+    // cycle, when every replica had integrated every edit.
+    let mut stable = frontier(&replicas.iter().collect::<Vec<_>>());
+
+    for _ in 0..num_cycles {
+        let mut edits = Vec::new();
+
+        for replica in &mut replicas {
+            let mut replica_edits = Vec::new();
+
+            for _ in 0..edits_per_cycle {
+                let edit = replica.random_edit(rng, 5, 5);
+                replica_edits.push(replica.edit(edit));
+            }
+
+            edits.push(replica_edits);
+        }
+
+        for (idx, replica) in replicas.iter_mut().enumerate() {
+            // Compact while some of the edits are still in flight.
+            if rng.random::<bool>() {
+                replica.crdt.compact(&stable);
+                replica.assert_invariants();
+            }
+
+            let mut others =
+                (0..num_replicas).filter(|&i| i != idx).collect::<Vec<_>>();
+
+            others.shuffle(rng);
+
+            for other in others {
+                for edit in &edits[other] {
+                    replica.merge(edit);
+                }
+            }
+        }
+
+        for replica in &replicas {
+            replica.assert_invariants();
+        }
+
+        assert_convergence!(replicas);
+
+        stable = frontier(&replicas.iter().collect::<Vec<_>>());
+    }
+}
@@ -1,6 +1,6 @@
 #[cfg(feature = "encode")]
 mod encode {
-    use cola::Replica;
+    use cola::{AnchorBias, Replica};
 
     /// Tests an encode-decode round-trip of an empty `Replica`.
     #[test]
@@ -30,4 +30,30 @@ mod encode {
 
         assert!(replica.eq_decoded(&decoded));
     }
+
+    /// Tests an encode-decode round-trip of a `Replica` that has been
+    /// compacted.
+    #[test]
+    fn encode_compacted() {
+        let mut replica1 = Replica::new(1, 10);
+        let mut replica2 = replica1.fork(2);
+
+        let insertion = replica2.inserted(5, 5);
+        let _ = replica1.integrate_insertion(&insertion);
+
+        let anchor = replica1.create_anchor(7, AnchorBias::Left);
+
+        let deletion = replica1.deleted(3..12);
+        let _ = replica2.integrate_deletion(&deletion);
+
+        let frontier = replica1.version().meet(&replica2.version());
+        assert_eq!(replica1.compact(&frontier).removed_runs(), 3);
+
+        let encoded = replica1.encode();
+
+        let decoded = Replica::decode(3, &encoded).unwrap();
+
+        assert!(replica1.eq_decoded(&decoded));
+        assert_eq!(decoded.resolve_anchor(anchor), Some(3));
+    }
 }
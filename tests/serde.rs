@@ -118,6 +118,25 @@ mod serde {
         test_trace::<2, SerdeJson>(traces::friends_forever());
     }
 
+    /// Tests a serialize-deserialize round-trip of a `Version`.
+    #[test]
+    fn serde_version_round_trip() {
+        let mut replica1 = cola::Replica::new(1, 10);
+        let mut replica2 = replica1.fork(2);
+
+        let insertion = replica2.inserted(3, 4);
+        let _ = replica1.integrate_insertion(&insertion);
+        let _ = replica1.deleted(2..8);
+
+        let version = replica1.version();
+
+        let encoded = SerdeJson::encode(&version);
+        assert_eq!(SerdeJson::decode::<cola::Version>(encoded), version);
+
+        let encoded = Bincode::encode(&version);
+        assert_eq!(Bincode::decode::<cola::Version>(encoded), version);
+    }
+
     /// Runs a trace and prints the total size of the serialized `Insertion`s
     /// and `Deletion`s.
     fn serde_sizes<E: Encoder>(trace: &SequentialTrace) {
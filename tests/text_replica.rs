@@ -0,0 +1,237 @@
+#[cfg(feature = "text-replica")]
+mod text_replica {
+    use cola::{Replica, ReplicaId, TextEdit, TextReplica};
+    use rand::seq::{IndexedRandom, SliceRandom};
+    use rand::{Rng, SeedableRng};
+    use rand_chacha::ChaCha8Rng;
+
+    /// Tests that offsets are interpreted as byte offsets even when the text
+    /// contains multi-byte characters.
+    #[test]
+    fn text_replica_multibyte() {
+        let mut peer1 = TextReplica::new(1, "àc");
+        let mut peer2 = peer1.fork(2);
+
+        let insert_b = peer1.insert(2, "b");
+        let insert_e = peer2.insert(3, "è");
+
+        peer1.integrate(&insert_e);
+        peer2.integrate(&insert_b);
+
+        assert_eq!(peer1.contents(), "àbcè");
+        assert_eq!(peer2.contents(), "àbcè");
+        assert_eq!(peer1.slice(2..3), "b");
+    }
+
+    /// Tests that a byte offset falling inside a multi-byte character is
+    /// rejected.
+    #[test]
+    #[should_panic]
+    fn text_replica_insert_not_char_boundary() {
+        let mut replica = TextReplica::new(1, "à");
+        let _ = replica.insert(1, "b");
+    }
+
+    /// Tests that integrating an insertion whose text is not as long as the
+    /// insertion itself is rejected.
+    #[test]
+    #[should_panic]
+    fn text_replica_integrate_text_len_mismatch() {
+        let mut peer1 = TextReplica::new(1, "ac");
+        let mut peer2 = peer1.fork(2);
+
+        let TextEdit::Insertion(insertion, _) = peer1.insert(1, "b") else {
+            unreachable!();
+        };
+
+        peer2.integrate(&TextEdit::Insertion(insertion, "bb".to_owned()));
+    }
+
+    /// Tests that a new peer can join a session by decoding the `Replica` of
+    /// an existing peer and pairing it with the contents of its document.
+    #[cfg(feature = "encode")]
+    #[test]
+    fn text_replica_from_decoded_parts() {
+        let mut peer1 = TextReplica::new(1, "àc");
+        let _ = peer1.insert(2, "b");
+
+        let encoded = peer1.replica().encode();
+        let replica = Replica::decode(2, &encoded).unwrap();
+        let mut peer2 =
+            TextReplica::from_parts(replica, peer1.rope().to_string());
+
+        let insert_d = peer1.insert(4, "d");
+        let insert_e = peer2.insert(0, "è");
+
+        peer1.integrate(&insert_e);
+        peer2.integrate(&insert_d);
+
+        assert_eq!(peer1.contents(), "èàbcd");
+        assert_eq!(peer2.contents(), "èàbcd");
+    }
+
+    /// Tests that pairing a `Replica` with a text of a different length is
+    /// rejected.
+    #[test]
+    #[should_panic]
+    fn text_replica_from_parts_len_mismatch() {
+        let _ = TextReplica::from_parts(Replica::new(1, 2), "àc");
+    }
+
+    /// Tests that the contents of backlogged insertions are applied once the
+    /// edits they depend on are integrated, and that deletions depending on
+    /// them are applied after them.
+    #[test]
+    fn text_replica_backlog() {
+        let mut peer1 = TextReplica::new(1, "");
+        let mut peer2 = peer1.fork(2);
+        let mut peer3 = peer1.fork(3);
+
+        let insert_hello = peer1.insert(0, "Hello");
+        let insert_world = peer1.insert(5, " world");
+
+        peer2.integrate(&insert_hello);
+        peer2.integrate(&insert_world);
+
+        let delete_hello = peer2.delete(..6);
+
+        peer3.integrate(&delete_hello);
+        peer3.integrate(&insert_world);
+        peer3.integrate(&insert_world);
+
+        assert_eq!(peer3.contents(), "");
+
+        peer3.integrate(&insert_hello);
+
+        assert_eq!(peer2.contents(), "world");
+        assert_eq!(peer3.contents(), "world");
+    }
+
+    /// Tests that integrating the same edit multiple times has no effect.
+    #[test]
+    fn text_replica_integrate_twice() {
+        let mut peer1 = TextReplica::new(1, "foo");
+        let mut peer2 = peer1.fork(2);
+
+        let insertion = peer1.insert(3, "bar");
+        let deletion = peer1.delete(0..1);
+
+        for _ in 0..2 {
+            peer2.integrate(&insertion);
+            peer2.integrate(&deletion);
+        }
+
+        assert_eq!(peer1.contents(), "oobar");
+        assert_eq!(peer2.contents(), "oobar");
+    }
+
+    /// Tests a serialize-deserialize round-trip of `TextEdit`s.
+    #[cfg(feature = "serde")]
+    #[test]
+    fn text_replica_serde() {
+        let mut peer1 = TextReplica::new(1, "àc");
+        let mut peer2 = peer1.fork(2);
+
+        let insertion = peer1.insert(2, "bè");
+        let deletion = peer1.delete(..2);
+
+        for edit in [insertion, deletion] {
+            let json = serde_json::to_string(&edit).unwrap();
+            let decoded = serde_json::from_str::<TextEdit>(&json).unwrap();
+            assert_eq!(edit, decoded);
+            peer2.integrate(&decoded);
+        }
+
+        assert_eq!(peer2.contents(), "bèc");
+    }
+
+    #[test]
+    fn text_replica_random() {
+        let seed = rand::random::<u64>();
+        println!("seed: {seed}");
+        let mut rng = ChaCha8Rng::seed_from_u64(seed);
+        test_text_replica_random(&mut rng, 4, 100, 5);
+    }
+
+    fn test_text_replica_random(
+        rng: &mut impl Rng,
+        num_replicas: usize,
+        num_cycles: usize,
+        edits_per_cycle: usize,
+    ) {
+        let first = TextReplica::new(1, "");
+
+        let mut replicas = vec![first];
+
+        for i in 1..num_replicas {
+            replicas.push(replicas[0].fork(i as ReplicaId + 1));
+        }
+
+        for _ in 0..num_cycles {
+            let edits = replicas
+                .iter_mut()
+                .flat_map(|replica| {
+                    (0..edits_per_cycle)
+                        .map(|_| random_edit(replica, rng))
+                        .collect::<Vec<_>>()
+                })
+                .collect::<Vec<_>>();
+
+            for replica in &mut replicas {
+                let mut edits = edits.iter().collect::<Vec<_>>();
+                edits.shuffle(rng);
+                for edit in edits {
+                    replica.integrate(edit);
+                }
+            }
+
+            for replica in &replicas[1..] {
+                assert_eq!(replica.contents(), replicas[0].contents());
+            }
+        }
+    }
+
+    fn random_edit(replica: &mut TextReplica, rng: &mut impl Rng) -> TextEdit {
+        let len = replica.len();
+
+        if len == 0 || rng.random::<bool>() {
+            let offset = random_char_boundary(replica, 0..=len, rng);
+            let letter = random_char(rng);
+            let text_len = rng.random_range(1..=5);
+            let text = (0..text_len).map(|_| letter).collect::<String>();
+            replica.insert(offset, text)
+        } else {
+            let start = random_char_boundary(replica, 0..=len - 1, rng);
+            let end = random_char_boundary(
+                replica,
+                start + 1..=(start + 10).min(len),
+                rng,
+            );
+            replica.delete(start..end)
+        }
+    }
+
+    /// Returns a random character which is 1, 2, 3 or 4 bytes long.
+    fn random_char(rng: &mut impl Rng) -> char {
+        match rng.random_range(0..4) {
+            0 => rng.random_range('a'..='z'),
+            1 => rng.random_range('à'..='ÿ'),
+            2 => rng.random_range('ぁ'..='ゖ'),
+            _ => rng.random_range('😀'..='😏'),
+        }
+    }
+
+    /// Returns a random byte offset in the given range which lies on a `char`
+    /// boundary of the replica's contents.
+    fn random_char_boundary(
+        replica: &TextReplica,
+        range: std::ops::RangeInclusive<usize>,
+        rng: &mut impl Rng,
+    ) -> usize {
+        let contents = replica.contents();
+        let boundaries = range
+            .filter(|&offset| contents.is_char_boundary(offset))
+            .collect::<Vec<_>>();
+        *boundaries.choose(rng).unwrap()
+    }
+}
@@ -0,0 +1,405 @@
+use std::collections::HashMap;
+
+use cola::{
+    Deletion,
+    Insertion,
+    Replica,
+    ReplicaId,
+    Text,
+    UndoEdit,
+    UndoManager,
+};
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+/// Maps every character ever inserted in a session to its contents.
+#[derive(Default)]
+struct Contents {
+    map: HashMap<(ReplicaId, usize), char>,
+}
+
+impl Contents {
+    fn get(&self, text: &Text) -> String {
+        text.temporal_range()
+            .map(|offset| self.map[&(text.inserted_by(), offset)])
+            .collect()
+    }
+
+    fn set(&mut self, text: &Text, contents: &str) {
+        for (offset, char) in text.temporal_range().zip(contents.chars()) {
+            self.map.insert((text.inserted_by(), offset), char);
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+enum Edit {
+    Insertion(Insertion),
+    Deletion(Deletion),
+}
+
+struct Peer {
+    buffer: String,
+    crdt: Replica,
+    undo: UndoManager,
+}
+
+impl Peer {
+    fn new(buffer: &str, contents: &mut Contents) -> Self {
+        let crdt = Replica::new(1, buffer.len());
+        for (offset, char) in buffer.chars().enumerate() {
+            contents.map.insert((crdt.id(), offset), char);
+        }
+        Self { buffer: buffer.to_owned(), crdt, undo: UndoManager::new() }
+    }
+
+    fn fork(&self, id: ReplicaId) -> Self {
+        Self {
+            buffer: self.buffer.clone(),
+            crdt: self.crdt.fork(id),
+            undo: UndoManager::new(),
+        }
+    }
+
+    fn insert(
+        &mut self,
+        offset: usize,
+        s: &str,
+        contents: &mut Contents,
+    ) -> Edit {
+        self.buffer.insert_str(offset, s);
+        let insertion = self.undo.inserted(&mut self.crdt, offset, s.len());
+        contents.set(insertion.text(), s);
+        Edit::Insertion(insertion)
+    }
+
+    fn delete(&mut self, range: std::ops::Range<usize>) -> Edit {
+        self.buffer.replace_range(range.clone(), "");
+        Edit::Deletion(self.undo.deleted(&mut self.crdt, range))
+    }
+
+    fn apply(
+        &mut self,
+        edits: Vec<UndoEdit>,
+        contents: &mut Contents,
+    ) -> Vec<Edit> {
+        edits
+            .into_iter()
+            .map(|edit| match edit {
+                UndoEdit::Insertion { insertion, at_offset, restores } => {
+                    let s = contents.get(&restores);
+                    contents.set(insertion.text(), &s);
+                    self.buffer.insert_str(at_offset, &s);
+                    Edit::Insertion(insertion)
+                },
+                UndoEdit::Deletion { deletion, range } => {
+                    self.buffer.replace_range(range, "");
+                    Edit::Deletion(deletion)
+                },
+            })
+            .collect()
+    }
+
+    fn undo(&mut self, contents: &mut Contents) -> Vec<Edit> {
+        let edits = self.undo.undo(&mut self.crdt);
+        self.apply(edits, contents)
+    }
+
+    fn redo(&mut self, contents: &mut Contents) -> Vec<Edit> {
+        let edits = self.undo.redo(&mut self.crdt);
+        self.apply(edits, contents)
+    }
+
+    fn merge(&mut self, edit: &Edit, contents: &Contents) {
+        match edit {
+            Edit::Insertion(insertion) => {
+                let offset = self.crdt.integrate_insertion(insertion).unwrap();
+                self.buffer
+                    .insert_str(offset, &contents.get(insertion.text()));
+            },
+            Edit::Deletion(deletion) => {
+                for range in
+                    self.crdt.integrate_deletion(deletion).into_iter().rev()
+                {
+                    self.buffer.replace_range(range, "");
+                }
+            },
+        }
+    }
+
+    fn merge_all(&mut self, edits: &[Edit], contents: &Contents) {
+        for edit in edits {
+            self.merge(edit, contents);
+        }
+    }
+}
+
+/// Tests that undoing an insertion deletes the inserted text, and that
+/// redoing it inserts it back.
+#[test]
+fn undo_redo_insertion() {
+    let mut contents = Contents::default();
+
+    let mut peer1 = Peer::new("Hello", &mut contents);
+    let mut peer2 = peer1.fork(2);
+
+    let insertion = peer1.insert(5, " world", &mut contents);
+    peer2.merge(&insertion, &contents);
+    assert_eq!(peer2.buffer, "Hello world");
+
+    let undo = peer1.undo(&mut contents);
+    peer2.merge_all(&undo, &contents);
+    assert_eq!(peer1.buffer, "Hello");
+    assert_eq!(peer2.buffer, "Hello");
+
+    let redo = peer1.redo(&mut contents);
+    peer2.merge_all(&redo, &contents);
+    assert_eq!(peer1.buffer, "Hello world");
+    assert_eq!(peer2.buffer, "Hello world");
+
+    assert!(peer1.undo.can_undo());
+    assert!(!peer1.undo.can_redo());
+}
+
+/// Tests that undoing a deletion re-inserts the deleted text.
+#[test]
+fn undo_redo_deletion() {
+    let mut contents = Contents::default();
+
+    let mut peer1 = Peer::new("Hello world", &mut contents);
+    let mut peer2 = peer1.fork(2);
+
+    let deletion = peer1.delete(0..6);
+    peer2.merge(&deletion, &contents);
+    assert_eq!(peer2.buffer, "world");
+
+    let undo = peer1.undo(&mut contents);
+    peer2.merge_all(&undo, &contents);
+    assert_eq!(peer1.buffer, "Hello world");
+    assert_eq!(peer2.buffer, "Hello world");
+
+    let redo = peer1.redo(&mut contents);
+    peer2.merge_all(&redo, &contents);
+    assert_eq!(peer1.buffer, "world");
+    assert_eq!(peer2.buffer, "world");
+}
+
+/// Tests that all the edits recorded before calling `end_group()` are undone
+/// together.
+#[test]
+fn undo_groups() {
+    let mut contents = Contents::default();
+
+    let mut peer = Peer::new("", &mut contents);
+
+    let _ = peer.insert(0, "a", &mut contents);
+    let _ = peer.insert(1, "b", &mut contents);
+    peer.undo.end_group();
+    let _ = peer.insert(2, "c", &mut contents);
+    let _ = peer.delete(0..1);
+
+    let _ = peer.undo(&mut contents);
+    assert_eq!(peer.buffer, "ab");
+
+    let _ = peer.undo(&mut contents);
+    assert_eq!(peer.buffer, "");
+    assert!(!peer.undo.can_undo());
+
+    let _ = peer.redo(&mut contents);
+    let _ = peer.redo(&mut contents);
+    assert_eq!(peer.buffer, "bc");
+    assert!(!peer.undo.can_redo());
+}
+
+/// Tests that recording a new edit clears the redo stack.
+#[test]
+fn undo_new_edit_clears_redo() {
+    let mut contents = Contents::default();
+
+    let mut peer = Peer::new("", &mut contents);
+
+    let _ = peer.insert(0, "a", &mut contents);
+    let _ = peer.undo(&mut contents);
+    assert!(peer.undo.can_redo());
+
+    let _ = peer.insert(0, "b", &mut contents);
+    assert!(!peer.undo.can_redo());
+    assert!(peer.undo.redo(&mut peer.crdt).is_empty());
+}
+
+/// Tests that undoing an insertion leaves the text inserted concurrently by
+/// other peers in place.
+#[test]
+fn undo_insertion_keeps_remote_edits() {
+    let mut contents = Contents::default();
+
+    let mut peer1 = Peer::new("", &mut contents);
+    let mut peer2 = peer1.fork(2);
+
+    let insertion = peer1.insert(0, "Hello world", &mut contents);
+    peer2.merge(&insertion, &contents);
+
+    let remote = peer2.insert(5, ",", &mut contents);
+    let undo = peer1.undo(&mut contents);
+
+    peer1.merge(&remote, &contents);
+    peer2.merge_all(&undo, &contents);
+
+    assert_eq!(peer1.buffer, ",");
+    assert_eq!(peer2.buffer, ",");
+}
+
+/// Tests that undoing a deletion re-inserts the deleted text around the text
+/// that was inserted concurrently in the deleted range.
+#[test]
+fn undo_deletion_around_remote_insertion() {
+    let mut contents = Contents::default();
+
+    let mut peer1 = Peer::new("abcdef", &mut contents);
+    let mut peer2 = peer1.fork(2);
+
+    let deletion = peer1.delete(1..5);
+    let insertion = peer2.insert(3, "X", &mut contents);
+
+    peer1.merge(&insertion, &contents);
+    peer2.merge(&deletion, &contents);
+
+    assert_eq!(peer1.buffer, "aXf");
+    assert_eq!(peer2.buffer, "aXf");
+
+    let undo = peer1.undo(&mut contents);
+    assert_eq!(undo.len(), 2);
+    peer2.merge_all(&undo, &contents);
+
+    assert_eq!(peer1.buffer, "abcXdef");
+    assert_eq!(peer2.buffer, "abcXdef");
+}
+
+/// Tests that undoing a deletion re-inserts the deleted text where it was even
+/// if another peer concurrently inserted some text at the same offset.
+#[test]
+fn undo_deletion_concurrent_insertion_at_boundary() {
+    let mut contents = Contents::default();
+
+    let mut peer1 = Peer::new("abc", &mut contents);
+    let mut peer2 = peer1.fork(2);
+
+    let deletion = peer1.delete(1..2);
+    let insertion = peer2.insert(1, "X", &mut contents);
+
+    let undo = peer1.undo(&mut contents);
+    assert_eq!(peer1.buffer, "abc");
+
+    peer1.merge(&insertion, &contents);
+    peer2.merge(&deletion, &contents);
+    peer2.merge_all(&undo, &contents);
+
+    assert_eq!(peer1.buffer, "aXbc");
+    assert_eq!(peer2.buffer, "aXbc");
+}
+
+/// Tests that undoing a deletion only re-inserts the text that was deleted
+/// by it, and not the text that had already been deleted by another peer.
+#[test]
+fn undo_deletion_over_remote_deletion() {
+    let mut contents = Contents::default();
+
+    let mut peer1 = Peer::new("abcdef", &mut contents);
+    let mut peer2 = peer1.fork(2);
+
+    let remote = peer2.delete(2..4);
+    peer1.merge(&remote, &contents);
+    assert_eq!(peer1.buffer, "abef");
+
+    let _ = peer1.delete(1..3);
+    assert_eq!(peer1.buffer, "af");
+
+    let _ = peer1.undo(&mut contents);
+    assert_eq!(peer1.buffer, "abef");
+}
+
+#[test]
+fn undo_random() {
+    let seed = rand::random::<u64>();
+    println!("seed: {seed}");
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+    test_undo_random(&mut rng, 3, 50, 5);
+}
+
+fn test_undo_random(
+    rng: &mut impl Rng,
+    num_peers: usize,
+    num_cycles: usize,
+    edits_per_cycle: usize,
+) {
+    let mut contents = Contents::default();
+
+    let first = Peer::new("", &mut contents);
+
+    let mut peers = vec![first];
+
+    for i in 1..num_peers {
+        peers.push(peers[0].fork(i as ReplicaId + 1));
+    }
+
+    for _ in 0..num_cycles {
+        let mut edits = Vec::new();
+
+        for peer in &mut peers {
+            let mut peer_edits = Vec::new();
+
+            for _ in 0..edits_per_cycle {
+                peer_edits.extend(random_edit(peer, rng, &mut contents));
+            }
+
+            edits.push(peer_edits);
+        }
+
+        // Each peer's edits are delivered in order, but the peers are
+        // shuffled.
+        for (idx, peer) in peers.iter_mut().enumerate() {
+            let mut others =
+                (0..num_peers).filter(|&i| i != idx).collect::<Vec<_>>();
+            others.shuffle(rng);
+            for other in others {
+                peer.merge_all(&edits[other], &contents);
+            }
+        }
+
+        for peer in &peers {
+            peer.crdt.assert_invariants();
+            assert_eq!(peer.buffer.len(), peer.crdt.len());
+            assert_eq!(peer.buffer, peers[0].buffer);
+        }
+    }
+}
+
+fn random_edit(
+    peer: &mut Peer,
+    rng: &mut impl Rng,
+    contents: &mut Contents,
+) -> Vec<Edit> {
+    let len = peer.buffer.len();
+
+    match rng.random_range(0..6) {
+        0 => peer.undo(contents),
+        1 => peer.redo(contents),
+        2 => {
+            peer.undo.end_group();
+            Vec::new()
+        },
+        3 if len > 0 => {
+            let start = rng.random_range(0..len);
+            let end = (start + rng.random_range(1..=5)).min(len);
+            vec![peer.delete(start..end)]
+        },
+        _ => {
+            let offset = rng.random_range(0..=len);
+            let letter = rng.random_range('a'..='z');
+            let text_len = rng.random_range(1..=5);
+            let text = (0..text_len).map(|_| letter).collect::<String>();
+            vec![peer.insert(offset, &text, contents)]
+        },
+    }
+}
@@ -0,0 +1,110 @@
+use cola::{Replica, Version};
+
+fn assert_synced(replica: &Replica, version: &Version) {
+    assert!(replica.missing_since(version).is_empty());
+}
+
+/// Tests that a `Replica` isn't missing any edits from its own `Version`.
+#[test]
+fn missing_since_own_version() {
+    let mut replica = Replica::new(1, 10);
+
+    let _ = replica.inserted(3, 2);
+    let _ = replica.deleted(0..4);
+
+    assert_synced(&replica, &replica.version());
+}
+
+/// Tests that the insertions and deletions performed by a single peer are
+/// reported as missing until the other peer integrates them.
+#[test]
+fn missing_since_local_edits() {
+    let mut replica1 = Replica::new(1, 10);
+    let mut replica2 = replica1.fork(2);
+
+    let ins_1 = replica1.inserted(2, 3);
+    let del_1 = replica1.deleted(4..6);
+    let ins_2 = replica1.inserted(0, 1);
+    let del_2 = replica1.deleted(..1);
+
+    let missing = replica1.missing_since(&replica2.version());
+
+    assert_eq!(missing.insertions().len(), 1);
+    assert_eq!(missing.insertions()[0].inserted_by(), 1);
+    assert_eq!(missing.insertions()[0].temporal_range(), 10..14);
+    assert_eq!(missing.deletions(), [(1, 1..3)]);
+
+    let _ = replica2.integrate_insertion(&ins_1);
+    let _ = replica2.integrate_deletion(&del_1);
+
+    let missing = replica1.missing_since(&replica2.version());
+
+    assert_eq!(missing.insertions()[0].temporal_range(), 13..14);
+    assert_eq!(missing.deletions(), [(1, 2..3)]);
+
+    let _ = replica2.integrate_insertion(&ins_2);
+    let _ = replica2.integrate_deletion(&del_2);
+
+    assert_synced(&replica1, &replica2.version());
+    assert_synced(&replica2, &replica1.version());
+}
+
+/// Tests that a peer also reports the edits it has received from other peers
+/// as missing, sorted by the `ReplicaId` of the peer that performed them.
+#[test]
+fn missing_since_relayed_edits() {
+    let mut replica1 = Replica::new(1, 10);
+    let mut replica2 = replica1.fork(2);
+    let mut replica3 = replica1.fork(3);
+
+    let ins_3 = replica3.inserted(5, 2);
+    let del_3 = replica3.deleted(0..1);
+
+    let _ = replica2.integrate_insertion(&ins_3);
+    let _ = replica2.integrate_deletion(&del_3);
+
+    let ins_2 = replica2.inserted(0, 4);
+
+    let missing = replica2.missing_since(&replica1.version());
+
+    let inserted_by = missing
+        .insertions()
+        .iter()
+        .map(|text| text.inserted_by())
+        .collect::<Vec<_>>();
+
+    assert_eq!(inserted_by, [2, 3]);
+    assert_eq!(missing.deletions(), [(3, 1..2)]);
+
+    // Peer 1 can catch up by applying only the missing edits.
+    let _ = replica1.integrate_insertion(&ins_3);
+    let _ = replica1.integrate_deletion(&del_3);
+    let _ = replica1.integrate_insertion(&ins_2);
+
+    assert_synced(&replica2, &replica1.version());
+}
+
+/// Tests that the missing edits are computed relative to the given `Version`
+/// only, so two peers that have diverged are both missing something.
+#[test]
+fn missing_since_concurrent_edits() {
+    let mut replica1 = Replica::new(1, 10);
+    let mut replica2 = replica1.fork(2);
+
+    let ins_1 = replica1.inserted(1, 1);
+    let ins_2 = replica2.inserted(9, 2);
+
+    let missing_at_2 = replica1.missing_since(&replica2.version());
+    let missing_at_1 = replica2.missing_since(&replica1.version());
+
+    assert_eq!(missing_at_2.insertions(), [ins_1.text().clone()]);
+    assert_eq!(missing_at_1.insertions(), [ins_2.text().clone()]);
+
+    // The meet of the two versions is missing the edits of both peers.
+    let meet = replica1.version().meet(&replica2.version());
+    assert_eq!(replica2.missing_since(&meet).insertions().len(), 1);
+
+    let _ = replica1.integrate_insertion(&ins_2);
+
+    assert_eq!(replica1.missing_since(&meet).insertions().len(), 2);
+}